@@ -30,37 +30,15 @@ fn read_user_input() -> Result<String> {
 }
 
 async fn translate_text(api_key: &str, text: &str) -> Result<String> {
-    let (api_url, request_json) = BigModel::<Chat>::new(ChatModelName::Glm4Flash.into())
-        .add_message(Message::new(
-            Role::System.into(),
-            Some(Context::SimpleContexts(
-                "你是专业的英文润色高手，你是澳大利亚法律学专业的研究生,但母语是中文，具有相当专业的英语能力。".to_string(),
-            )),
-            None,
-        ))
-        .add_message(Message::new(
-            Role::User.into(),
-            Some(Context::SimpleContexts(
-                "我将发送一段不怎么专业的英文，你需要将这些内容使用你的专业技能进行润色。".to_string(),
-            )),
-            None,
-        ))
-        .add_message(Message::new(
-            Role::Assistant.into(),
-            Some(Context::SimpleContexts(
-                "好的，我会基于我的身份-澳大利亚法律学专业的研究生，使用我的专业技能进行润色。".to_string(),
-            )),
-            None,
-        ))
-        .add_message(Message::new(
-            Role::User.into(),
-            Some(Context::SimpleContexts(text.to_string())),
-            None,
-        ))
+    let (api_url, request_json) = BigModel::<Chat>::new(ChatModelName::Glm4Flash)
+        .add_message(Message::system("你是专业的英文润色高手，你是澳大利亚法律学专业的研究生,但母语是中文，具有相当专业的英语能力。"))
+        .add_message(Message::user("我将发送一段不怎么专业的英文，你需要将这些内容使用你的专业技能进行润色。"))
+        .add_message(Message::assistant("好的，我会基于我的身份-澳大利亚法律学专业的研究生，使用我的专业技能进行润色。"))
+        .add_message(Message::user(Context::SimpleContexts(text.to_string())))
         .max_tokens(4096)
         .build();
 
-    let response = post(api_url, api_key, request_json.to_json()).await?;
+    let response = post(api_url, api_key, request_json.to_json()?).await?;
     let context = chat_response_context(response)
         .await
         .expect("Cannot get context");
@@ -85,10 +63,10 @@ fn extract_content(input: &str) -> Result<String> {
         .map_err(|e| anyhow!("Failed to create regex: {:?}", e))?;
 
     // 使用正则表达式进行匹配，并提取第一个捕获组
-    if let Some(caps) = re.captures(input) {
-        if let Some(content) = caps.get(1) {
-            return Ok(content.as_str().to_string());
-        }
+    if let Some(caps) = re.captures(input)
+        && let Some(content) = caps.get(1)
+    {
+        return Ok(content.as_str().to_string());
     }
 
     Err(anyhow!("No content found after 'Content:'"))
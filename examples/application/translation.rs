@@ -1,11 +1,19 @@
 use anyhow::{Result, anyhow};
-use regex::RegexBuilder;
 use std::io::{self, BufRead, Write};
 use zhipuai_rs::prelude::*;
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let api_key = user_key()?;
+    let client = ZhipuClient::new(&api_key);
+
+    // 会话自动保存历史，只保留最近几轮以控制 token 用量
+    let mut conversation = Conversation::new(ChatModelName::Glm4Flash.as_str())
+        .system_prompt(
+            "你是专业的中译英翻译专家，将user发给你的中文翻译成英文，给你的英文翻译成中文，只回复翻译内容",
+        )
+        .history_limit(HistoryLimit::Turns(4));
+    conversation.request_mut().max_tokens(4096);
 
     let stdin = io::stdin();
     let mut handle = stdin.lock();
@@ -17,7 +25,7 @@ async fn main() -> Result<()> {
         // 读取用户输入的多行文本
         let mut input_lines = Vec::new();
         let mut line = String::new();
-        while let Ok(_) = handle.read_line(&mut line) {
+        while handle.read_line(&mut line).is_ok() {
             let trimmed_line = line.trim();
             if trimmed_line.eq_ignore_ascii_case("exit") {
                 println!("退出程序...");
@@ -38,80 +46,28 @@ async fn main() -> Result<()> {
 
         let input = input_lines.join("\n");
 
-        match translate_text(api_key.as_str(), &input).await {
-            Ok(translated) => {
-                let translation_result = extract_content(&translated).expect("无法提取内容");
-                println!("翻译结果:\n{}\n", translation_result);
-            }
+        match translate_text(&client, &mut conversation, &input).await {
+            Ok(translated) => println!("翻译结果:\n{}\n", translated),
             Err(err) => println!("翻译失败: {:?}", err),
         }
     }
 }
 
-async fn translate_text(api_key: &str, text: &str) -> Result<String> {
-    let (api_url, request_json) = BigModel::<Chat>::new(ChatModelName::Glm4Flash.into())
-        .add_message(Message::new(
-            Role::System.into(),
-            Some(Context::SimpleContexts(
-                "你是专业的中译英翻译专家，将user发给你的中文翻译成英文，给你的英文翻译成中文"
-                    .to_string(),
-            )),
-            None,
-        ))
-        .add_message(Message::new(
-            Role::User.into(),
-            Some(Context::SimpleContexts(
-                "将我发给你的中文翻译成英文，给你的英文翻译成中文，只回复翻译内容".to_string(),
-            )),
-            None,
-        ))
-        .add_message(Message::new(
-            Role::Assistant.into(),
-            Some(Context::SimpleContexts(
-                "接下来不管你发什么内容，我都只会进行翻译".to_string(),
-            )),
-            None,
-        ))
-        .add_message(Message::new(
-            Role::User.into(),
-            Some(Context::SimpleContexts(text.to_string())),
-            None,
-        ))
-        .max_tokens(4096)
-        .build();
-
-    let response = post(api_url, api_key, request_json.to_json()).await?;
-    let context = chat_response_context(response)
-        .await
-        .expect("无法获取上下文");
-
-    match context.get_choices() {
-        Some(choices) if !choices.is_empty() => {
-            let translations: Vec<String> = choices
-                .iter()
-                .map(|choice| format!("{}", choice.message()))
-                .collect();
-            Ok(translations.join("\n"))
-        }
-        _ => Err(anyhow!("未找到翻译")),
+async fn translate_text(
+    client: &ZhipuClient,
+    conversation: &mut Conversation,
+    text: &str,
+) -> Result<String> {
+    conversation.send(client, text).await?;
+    match conversation
+        .last()
+        .and_then(|message| message.simple_context())
+    {
+        Some((_, translation)) => Ok(translation.to_string()),
+        None => Err(anyhow!("未找到翻译")),
     }
 }
 
-fn extract_content(input: &str) -> Result<String> {
-    let re = RegexBuilder::new(r"(?m)Content:\s*(.*)")
-        .dot_matches_new_line(true)
-        .build()
-        .map_err(|e| anyhow!("无法创建正则表达式: {:?}", e))?;
-
-    if let Some(caps) = re.captures(input) {
-        if let Some(content) = caps.get(1) {
-            return Ok(content.as_str().to_string());
-        }
-    }
-
-    Err(anyhow!("在 'Content:' 后未找到内容"))
-}
-
 //noinspection SpellCheckingInspection
 fn user_key() -> Result<String> {
     // 首先尝试从环境变量获取
@@ -4,6 +4,7 @@ use zhipuai_rs::{chat_simple_message, prelude::*};
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let api_key = user_key()?;
+    let client = ZhipuClient::new(&api_key);
 
     let mut messages = Messages::new()
         .add_message(chat_simple_message!(
@@ -18,13 +19,12 @@ async fn main() -> anyhow::Result<()> {
         .add_message(chat_simple_message!(Role::User, "专家你好"));
 
     loop {
-        let (api_url, request_json) = BigModel::<Chat>::new(ChatModelName::Glm4Flash250414.into())
+        let (_, request_json) = BigModel::<Chat>::new(ChatModelName::Glm4Flash250414)
             .add_messages(messages.clone())
             .build();
-        println!("{:?}", request_json.to_json());
-        let response = post(&api_url, &api_key, request_json.to_json()).await?;
+        println!("{:?}", request_json.to_json()?);
 
-        match chat_response_context(response).await {
+        match client.chat(&request_json).await {
             Ok(context) => {
                 if let Some(choices) = context.get_choices() {
                     for choice in choices {
@@ -4,7 +4,7 @@ use zhipuai_rs::prelude::*;
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let api_key = user_key()?;
-    let (api_url, request_json) = BigModel::<Chat>::new(ChatModelName::CodeGeeX.into())
+    let (api_url, request_json) = BigModel::<Chat>::new(ChatModelName::CodeGeeX)
         .add_code_context(Extra::new(Target::new(
             Some("main.rs".to_string()),
             Some("Rust".to_string()),
@@ -14,7 +14,7 @@ async fn main() -> anyhow::Result<()> {
         .max_tokens(4096)
         .build();
 
-    let response = post(api_url, api_key, request_json.to_json()).await?;
+    let response = post(api_url, api_key, request_json.to_json()?).await?;
 
     match chat_response_context(response).await {
         Ok(context) => {
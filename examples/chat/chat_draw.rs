@@ -5,28 +5,30 @@ use zhipuai_rs::prelude::*;
 async fn main() -> anyhow::Result<()> {
     let api_key = user_key()?;
     let tool = DrawingTool;
-    let (api_url, request_json) = BigModel::<Chat>::new(ChatModelName::Glm4AllTools.into())
-        .add_message(Message::new(
-            Role::User.into(),
-            Some(Context::rich_contexts(RichContent::text(
-                "生成一个 hello kitty 的Melody风格 壁纸",
-            ))),
-            None,
-        ))
+    let (api_url, request_json) = BigModel::<Chat>::new(ChatModelName::Glm4AllTools)
+        .add_message(Message::user(Context::rich_contexts(RichContent::text(
+            "生成一个 hello kitty 的Melody风格 壁纸",
+        ))))
         .add_tools(Tool::new().drawing_tool(tool))
         .stream_enable(true)
         .build();
 
-    println!("{:?}", request_json.to_json());
+    println!("{:?}", request_json.to_json()?);
 
-    let response = post(api_url, api_key, request_json.to_json()).await?;
+    let response = post(api_url, api_key, request_json.to_json()?).await?;
 
     let stream = response_context_stream(response);
     tokio::pin!(stream);
 
     while let Some(result) = stream.next().await {
         match result {
-            Ok(data) => println!("{}", data),
+            Ok(ChatStreamEvent::ContentDelta(text)) => print!("{}", text),
+            Ok(ChatStreamEvent::ToolCallDelta {
+                name,
+                arguments_fragment,
+                ..
+            }) => println!("{} {}", name.unwrap_or_default(), arguments_fragment),
+            Ok(_) => {}
             Err(e) => eprintln!("Error: {}", e),
         }
     }
@@ -26,32 +26,37 @@ async fn main() -> anyhow::Result<()> {
     );
     let parameters = Parameters::new(hash);
 
-    let (api_url, request_json) = BigModel::<Chat>::new(ChatModelName::Glm4Flash.into())
-        .add_message(Message::new(
-            Role::User.into(),
-            Some(Context::simple_context("6月1日深圳到郴州的高铁")),
-            None,
-        ))
-        .add_tools(Tool::new().function(Function::new(
+    let registry = ToolRegistry::new().register(
+        Function::new(
             "query_train_info",
-            "根据用户提供的信息进行处理",
+            "根据用户提供的信息查询火车票",
             parameters,
-        )))
-        .build();
+        ),
+        |arguments: serde_json::Value| async move {
+            Ok(format!(
+                "{} {} 到 {} 的{}: G6113 08:00 发车, 余票充足",
+                arguments["日期"], arguments["起始地"], arguments["目的地"], arguments["类型"]
+            ))
+        },
+    );
+    let runner = ToolRunner::new(registry)
+        .max_iterations(4)
+        .validate_arguments(true);
 
-    let response = post(api_url, api_key, request_json.to_json()).await?;
+    let client = ZhipuClient::new(&api_key);
+    let mut conversation = Conversation::new(ChatModelName::Glm4Flash.as_str());
+    let run = runner
+        .send(&client, &mut conversation, "6月1日深圳到郴州的高铁")
+        .await?;
 
-    match chat_response_context(response).await {
-        Ok(context) => {
-            if let Some(choices) = context.get_choices() {
-                for choice in choices {
-                    println!("{}", choice.message());
-                }
-            }
-        }
-        Err(err) => {
-            println!("{:?}", err);
-        }
+    for record in &run.transcript {
+        println!(
+            "{}({}) -> {:?}",
+            record.invocation.name, record.invocation.arguments, record.outcome
+        );
+    }
+    if let Some(answer) = conversation.last() {
+        println!("{}", answer);
     }
 
     Ok(())
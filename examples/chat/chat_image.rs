@@ -1,28 +1,18 @@
-use base64::prelude::*;
 use std::io::{self, Write};
-use tokio::fs::read;
 use zhipuai_rs::prelude::*;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let api_key = user_key()?;
-    let data = read("examples/assets/video_frame.jpg").await?;
-    let image_url = BASE64_STANDARD.encode(&data);
-    let (api_url, request_json) = BigModel::<Chat>::new(ChatModelName::Glm4p6VFlash.into())
-        .add_message(Message::new(
-            Role::User.into(),
-            Some(
-                Context::rich_contexts(RichContent::image_url(
-                    // "https://sfile.chatglm.cn/testpath/8b01b0b4-51fd-5b51-90a1-3ad8fec8b00d_0.png",
-                    &image_url,
-                ))
+    let (api_url, request_json) = BigModel::<Chat>::new(ChatModelName::Glm4p6VFlash)
+        .add_message(Message::user(
+            // or RichContent::image_url("https://sfile.chatglm.cn/testpath/8b01b0b4-51fd-5b51-90a1-3ad8fec8b00d_0.png")
+            Context::rich_contexts(RichContent::image_file("examples/assets/video_frame.jpg")?)
                 .rich_content(RichContent::text("图里面有什么")),
-            ),
-            None,
         ))
         .build();
 
-    let response = post(api_url, api_key, request_json.to_json()).await?;
+    let response = post(api_url, api_key, request_json.to_json()?).await?;
 
     match chat_response_context(response).await {
         Ok(context) => {
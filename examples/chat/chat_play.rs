@@ -5,26 +5,28 @@ use zhipuai_rs::prelude::*;
 async fn main() -> anyhow::Result<()> {
     let api_key = user_key()?;
     let tool = DrawingTool;
-    let (api_url, request_json) = BigModel::<Chat>::new(ChatModelName::CharGlm4.into())
-        .add_message(Message::new(
-            Role::User.into(),
-            Some(Context::SimpleContexts("你好呀，我今天很开心".to_string())),
-            None,
-        ))
+    let (api_url, request_json) = BigModel::<Chat>::new(ChatModelName::CharGlm4)
+        .add_message(Message::user("你好呀，我今天很开心"))
         .add_tools(Tool::new().drawing_tool(tool))
         .stream_enable(true)
         .build();
 
-    println!("{:?}", request_json.to_json());
+    println!("{:?}", request_json.to_json()?);
 
-    let response = post(api_url, api_key, request_json.to_json()).await?;
+    let response = post(api_url, api_key, request_json.to_json()?).await?;
 
     let stream = response_context_stream(response);
     tokio::pin!(stream);
 
     while let Some(result) = stream.next().await {
         match result {
-            Ok(data) => println!("{}", data),
+            Ok(ChatStreamEvent::ContentDelta(text)) => print!("{}", text),
+            Ok(ChatStreamEvent::ToolCallDelta {
+                name,
+                arguments_fragment,
+                ..
+            }) => println!("{} {}", name.unwrap_or_default(), arguments_fragment),
+            Ok(_) => {}
             Err(e) => eprintln!("Error: {}", e),
         }
     }
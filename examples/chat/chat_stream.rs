@@ -4,33 +4,22 @@ use zhipuai_rs::prelude::*;
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let api_key = user_key()?;
-    let (api_url, request_json) = BigModel::<Chat>::new(ChatModelName::GlmZeroPreview.into())
-        .add_message(Message::new(
-            Role::System.into(),
-            Some(Context::SimpleContexts(
-                "Please think deeply before your response".to_string(),
-            )),
-            None,
+    let client = ZhipuClient::new(&api_key);
+    let stream = BigModel::<Chat>::new(ChatModelName::GlmZeroPreview)
+        .add_message(Message::system("Please think deeply before your response"))
+        .add_message(Message::user(
+            "一个袋子中有5个红球和3个蓝球,随机抽取2个球,抽到至少1个红球的概率为:",
         ))
-        .add_message(Message::new(
-            Role::User.into(),
-            Some(Context::SimpleContexts(
-                "一个袋子中有5个红球和3个蓝球,随机抽取2个球,抽到至少1个红球的概率为:".to_string(),
-            )),
-            None,
-        ))
-        .stream_enable(true)
         .max_tokens(12000)
-        .build();
-
-    let response = post(api_url, api_key, request_json.to_json()).await?;
-
-    let stream = response_context_stream(response);
+        .stream(&client);
     tokio::pin!(stream);
 
     while let Some(result) = stream.next().await {
         match result {
-            Ok(data) => print!("{}", data),
+            Ok(ChatStreamEvent::ReasoningDelta(text) | ChatStreamEvent::ContentDelta(text)) => {
+                print!("{}", text)
+            }
+            Ok(_) => {}
             Err(e) => eprintln!("Error: {}", e),
         }
     }
@@ -6,41 +6,43 @@ async fn main() -> anyhow::Result<()> {
     let api_key = user_key()?;
 
     // 使用支持 thinking 功能的 GLM-4.6 模型，并启用思维链推理
-    let (api_url, request_json) = BigModel::<Chat>::new(ChatModelName::Glm4p6.into())
-        .add_message(Message::new(
-            Role::System.into(),
-            Some(Context::SimpleContexts(
-                "你是一个数学专家，请仔细分析问题并展示你的推理过程。".to_string(),
-            )),
-            None,
-        ))
-        .add_message(Message::new(
-            Role::User.into(),
-            Some(Context::SimpleContexts(
-                "一个袋子中有5个红球和3个蓝球，随机抽取2个球，抽到至少1个红球的概率是多少？请详细说明计算过程。".to_string(),
-            )),
-            None,
-        ))
+    let (api_url, request_json) = BigModel::<Chat>::new(ChatModelName::Glm4p6)
+        .add_message(Message::system("你是一个数学专家，请仔细分析问题并展示你的推理过程。"))
+        .add_message(Message::user("一个袋子中有5个红球和3个蓝球，随机抽取2个球，抽到至少1个红球的概率是多少？请详细说明计算过程。"))
         .thinking_enable()  // 启用思维链推理，模型会展示推理过程
         .max_tokens(4000)
         .stream_enable(true)
         .build();
 
     println!("=== 启用思维链推理模式 ===");
-    println!("请求JSON: {}", request_json.to_json());
+    println!("请求JSON: {}", request_json.to_json()?);
     println!("\n=== 模型响应 ===");
 
-    let response = post(api_url, api_key, request_json.to_json()).await?;
+    let response = post(api_url, api_key, request_json.to_json()?).await?;
     let stream = response_context_stream(response);
     tokio::pin!(stream);
-    while let Some(item) = stream.next().await {
-        let item = item?;
-        print!("{}", item);
-        if item.find('\n').is_some() {
-            io::stdout().flush()?;
+    let mut thinking = false;
+    while let Some(event) = stream.next().await {
+        match event? {
+            ChatStreamEvent::ReasoningDelta(text) => {
+                if !thinking {
+                    println!("<think>");
+                    thinking = true;
+                }
+                print!("{}", text);
+            }
+            ChatStreamEvent::ContentDelta(text) => {
+                if thinking {
+                    println!("\n</think>");
+                    thinking = false;
+                }
+                print!("{}", text);
+            }
+            _ => {}
         }
+        io::stdout().flush()?;
     }
-    io::stdout().flush()?;
+    println!();
 
     Ok(())
 }
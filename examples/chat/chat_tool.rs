@@ -0,0 +1,55 @@
+use std::io::{self, Write};
+use zhipuai_rs::prelude::*;
+
+/// 根据出发地、目的地和日期查询火车票
+#[zhipu_tool]
+async fn query_train_info(
+    #[param(description = "出发城市或车站")] from: String,
+    #[param(description = "目的地城市或车站")] to: String,
+    #[param(schema = Property::string("要查询的火车日期").format("date"))] date: String,
+    #[param(description = "类型。火车、高铁、动车")] kind: Option<String>,
+) -> anyhow::Result<Vec<String>> {
+    Ok(vec![format!(
+        "{} {} 到 {} 的{}: G6113 08:00 发车, 余票充足",
+        date,
+        from,
+        to,
+        kind.as_deref().unwrap_or("火车")
+    )])
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let api_key = user_key()?;
+    let client = ZhipuClient::new(&api_key);
+    let runner = ToolRunner::new(query_train_info::register(ToolRegistry::new()));
+
+    let mut conversation = Conversation::new(ChatModelName::Glm4Flash.as_str());
+    let run = runner
+        .send(&client, &mut conversation, "6月1日深圳到郴州的高铁")
+        .await?;
+
+    for record in &run.transcript {
+        println!("{} -> {:?}", record.invocation.name, record.outcome);
+    }
+    if let Some(answer) = conversation.last() {
+        println!("{}", answer);
+    }
+
+    Ok(())
+}
+
+//noinspection SpellCheckingInspection
+fn user_key() -> anyhow::Result<String> {
+    // 首先尝试从环境变量获取
+    if let Ok(key) = std::env::var("ZHIPU_API_KEY") {
+        println!("从环境变量:ZHIPU_API_KEY 获取到key");
+        return Ok(key);
+    }
+    // 如果环境变量不存在，则要求用户输入
+    let mut input = String::new();
+    print!("输入你的key: ");
+    io::stdout().flush()?;
+    io::stdin().read_line(&mut input)?;
+    Ok(input.trim().to_string())
+}
@@ -14,19 +14,22 @@ async fn main() -> anyhow::Result<()> {
             "一个袋子中有5个红球和3个蓝球,随机抽取2个球,抽到至少1个红球的概率为:"
         ));
 
-    let (api_url, request_json) = BigModel::<Chat>::new(ChatModelName::GlmZ1Flash.into())
+    let (api_url, request_json) = BigModel::<Chat>::new(ChatModelName::GlmZ1Flash)
         .add_messages(messages)
         .stream_enable(true)
         .build();
 
-    let response = post(api_url, api_key, request_json.to_json()).await?;
+    let response = post(api_url, api_key, request_json.to_json()?).await?;
 
     let stream = response_context_stream(response);
     tokio::pin!(stream);
 
     while let Some(result) = stream.next().await {
         match result {
-            Ok(data) => print!("{}", data),
+            Ok(ChatStreamEvent::ReasoningDelta(text) | ChatStreamEvent::ContentDelta(text)) => {
+                print!("{}", text)
+            }
+            Ok(_) => {}
             Err(e) => eprint!("Error: {}", e),
         }
     }
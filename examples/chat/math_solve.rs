@@ -12,21 +12,17 @@ async fn main() -> anyhow::Result<()> {
         .read_to_end(&mut data)
         .await?;
     let image_url = BASE64_STANDARD.encode(&data);
-    let (api_url, request_json) = BigModel::<Chat>::new(ChatModelName::Glm4p1VThinkingFlashX.into())
-        .add_message(Message::new(
-            Role::User.into(),
-            Some(
-                Context::rich_contexts(RichContent::image_url(
-                    // "https://sfile.chatglm.cn/testpath/8b01b0b4-51fd-5b51-90a1-3ad8fec8b00d_0.png",
-                    &image_url,
-                ))
-                .rich_content(RichContent::text("理解题目，解决题目中的所有问题")),
-            ),
-            None,
+    let (api_url, request_json) = BigModel::<Chat>::new(ChatModelName::Glm4p1VThinkingFlashX)
+        .add_message(Message::user(
+            Context::rich_contexts(RichContent::image_url(
+                // "https://sfile.chatglm.cn/testpath/8b01b0b4-51fd-5b51-90a1-3ad8fec8b00d_0.png",
+                &image_url,
+            ))
+            .rich_content(RichContent::text("理解题目，解决题目中的所有问题")),
         ))
         .build();
 
-    let response = post(api_url, api_key, request_json.to_json()).await?;
+    let response = post(api_url, api_key, request_json.to_json()?).await?;
 
     match chat_response_context(response).await {
         Ok(context) => {
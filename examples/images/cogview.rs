@@ -6,15 +6,12 @@ use zhipuai_rs::prelude::*;
 async fn main() -> anyhow::Result<()> {
     let api_key = user_key()?;
 
-    let (api_url, request_json) =
-        BigModel::<images::Images>::new(images::model::ImagesModelName::CogView3Flash.into())
+    let client = ZhipuClient::new(&api_key);
+    let api_response =
+        BigModel::<images::Images>::new(images::model::ImagesModelName::CogView3Flash)
             .prompt("一只凶狠的猫咪。")
-            .build();
-
-    let response = post(api_url, api_key, request_json.to_json()).await?;
-    println!("come here post response");
-    let api_response = images::response::images_response_context(response).await?;
-    println!("come here post response decode");
+            .send(&client)
+            .await?;
     let url = api_response.urls();
 
     println!("图片链接: {:?}", url);
@@ -119,7 +119,8 @@ async fn main() -> anyhow::Result<()> {
         println!("{:?}", event);
     }
 
-    Ok(player.sleep_until_end())
+    player.sleep_until_end();
+    Ok(())
 }
 
 #[inline]
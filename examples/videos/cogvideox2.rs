@@ -12,7 +12,7 @@ async fn main() -> anyhow::Result<()> {
 
     // 构建视频生成请求
     let (api_url, request_json) =
-        BigModel::<videos::Videos>::new(videos::model::VideosModelName::Cogvideox2.into())
+        BigModel::<videos::Videos>::new(videos::model::VideosModelName::Cogvideox2)
             .prompt("用刀切铁块，丝滑切开。解压视频")
             .size(videos::VideoSize::Size3840x2160)
             .fps(videos::VideoFPS::VideoFps60)
@@ -20,7 +20,7 @@ async fn main() -> anyhow::Result<()> {
             .build();
 
     // 发送请求并获取响应
-    let response = post(api_url.clone(), api_key.clone(), request_json.to_json()).await?;
+    let response = post(api_url.clone(), api_key.clone(), request_json.to_json()?).await?;
 
     // 解析响应并获取任务 ID
     let api_response = videos::response::videos_response_context(response).await?;
@@ -37,7 +37,7 @@ async fn main() -> anyhow::Result<()> {
         async move {
             // 构建异步请求
             let (api_url, _) =
-                BigModel::<videos::Videos>::new(videos::model::VideosModelName::Cogvideox2.into())
+                BigModel::<videos::Videos>::new(videos::model::VideosModelName::Cogvideox2)
                     .prompt("")
                     .response_id(&task_id)
                     .build_response();
@@ -0,0 +1,283 @@
+//! # Model capabilities
+//!
+//! Every known model of [`ChatModelName`], [`ImagesModelName`] and [`VideosModelName`] has a
+//! [`ModelInfo`]: its wire name, context window, output limit and the [`Feature`]s it accepts, as
+//! documented by the platform. The
+//! request builders check their settings against it, see
+//! [`ChatApiRequest::validate`](super::chat::ChatApiRequest::validate). Custom model names have no
+//! entry and are never checked.
+
+use super::{chat::ChatModelName, images::ImagesModelName, videos::VideosModelName};
+use std::fmt;
+
+/// Something a request may ask of a model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Feature {
+    /// images in the messages, or an image to start a video from
+    VisionInput,
+    /// videos in the messages
+    VideoInput,
+    /// audio clips in the messages
+    AudioInput,
+    /// function, retrieval and other tools
+    Tools,
+    /// the `thinking` parameter
+    Thinking,
+    /// streamed responses
+    Stream,
+    /// the `web_search` tool
+    WebSearch,
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::VisionInput => "image input",
+            Self::VideoInput => "video input",
+            Self::AudioInput => "audio input",
+            Self::Tools => "tools",
+            Self::Thinking => "thinking",
+            Self::Stream => "streaming",
+            Self::WebSearch => "web search",
+        })
+    }
+}
+
+/// What a model accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModelInfo {
+    name: &'static str,
+    context_window: u32,
+    max_output_tokens: u32,
+    vision: bool,
+    video: bool,
+    audio: bool,
+    tools: bool,
+    thinking: bool,
+    stream: bool,
+    web_search: bool,
+}
+
+impl ModelInfo {
+    /// A chat model with streaming and no other feature.
+    pub(crate) const fn new(
+        name: &'static str,
+        context_window: u32,
+        max_output_tokens: u32,
+    ) -> Self {
+        Self {
+            name,
+            context_window,
+            max_output_tokens,
+            vision: false,
+            video: false,
+            audio: false,
+            tools: false,
+            thinking: false,
+            stream: true,
+            web_search: false,
+        }
+    }
+
+    /// An image or video model: no token limits and no streaming.
+    pub(crate) const fn media(name: &'static str) -> Self {
+        let mut info = Self::new(name, 0, 0);
+        info.stream = false;
+        info
+    }
+
+    pub(crate) const fn vision(mut self) -> Self {
+        self.vision = true;
+        self
+    }
+
+    pub(crate) const fn video(mut self) -> Self {
+        self.video = true;
+        self
+    }
+
+    pub(crate) const fn audio(mut self) -> Self {
+        self.audio = true;
+        self
+    }
+
+    pub(crate) const fn tools(mut self) -> Self {
+        self.tools = true;
+        self
+    }
+
+    pub(crate) const fn thinking(mut self) -> Self {
+        self.thinking = true;
+        self
+    }
+
+    pub(crate) const fn web_search(mut self) -> Self {
+        self.web_search = true;
+        self
+    }
+
+    /// the name sent to the API
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// the tokens of prompt and completion together, 0 for image and video models
+    pub fn context_window(&self) -> u32 {
+        self.context_window
+    }
+
+    /// the largest accepted `max_tokens`, 0 for image and video models
+    pub fn max_output_tokens(&self) -> u32 {
+        self.max_output_tokens
+    }
+
+    pub fn supports(&self, feature: Feature) -> bool {
+        match feature {
+            Feature::VisionInput => self.vision,
+            Feature::VideoInput => self.video,
+            Feature::AudioInput => self.audio,
+            Feature::Tools => self.tools,
+            Feature::Thinking => self.thinking,
+            Feature::Stream => self.stream,
+            Feature::WebSearch => self.web_search,
+        }
+    }
+}
+
+/// The [`ModelInfo`] of any known chat, image or video model.
+/// ```ignore
+/// let info = model_info("glm-4-flash").unwrap();
+/// assert!(!info.supports(Feature::Thinking));
+/// ```
+pub fn model_info(name: &str) -> Option<ModelInfo> {
+    let name = name.trim();
+    name.parse::<ChatModelName>()
+        .ok()
+        .and_then(|model| model.info())
+        .or_else(|| name.parse::<ImagesModelName>().ok()?.info())
+        .or_else(|| name.parse::<VideosModelName>().ok()?.info())
+}
+
+/// Declare a model name enum whose wire names, `Display`, `FromStr` and serde form come from one
+/// table, plus a `Custom(String)` variant for names the crate does not know yet. Aliases after
+/// `|` are accepted by `FromStr` only.
+macro_rules! model_names {
+    (
+        $(#[$meta:meta])*
+        $vis:vis enum $name:ident {
+            $($(#[$variant_meta:meta])* $variant:ident => $wire:literal $(| $alias:literal)*,)*
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, PartialEq, Eq, Clone, Hash)]
+        $vis enum $name {
+            $($(#[$variant_meta])* $variant,)*
+            /// any other model name, sent as is
+            Custom(String),
+        }
+
+        impl $name {
+            /// every known model
+            pub const ALL: &'static [$name] = &[$($name::$variant),*];
+
+            /// the name sent to the API
+            pub fn as_str(&self) -> &str {
+                match self {
+                    Self::Custom(name) => name,
+                    known => known.static_name().unwrap_or_default(),
+                }
+            }
+
+            /// the wire name of a known model
+            pub(crate) fn static_name(&self) -> Option<&'static str> {
+                match self {
+                    $(Self::$variant => Some($wire),)*
+                    Self::Custom(_) => None,
+                }
+            }
+        }
+
+        impl std::fmt::Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
+
+        impl AsRef<str> for $name {
+            fn as_ref(&self) -> &str {
+                self.as_str()
+            }
+        }
+
+        impl From<$name> for String {
+            fn from(model: $name) -> String {
+                match model {
+                    $name::Custom(name) => name,
+                    model => model.as_str().to_string(),
+                }
+            }
+        }
+
+        impl std::str::FromStr for $name {
+            type Err = std::convert::Infallible;
+
+            /// Known names and aliases are matched ignoring case, anything else is `Custom`.
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                let s = s.trim();
+                $(
+                    if s.eq_ignore_ascii_case($wire) $(|| s.eq_ignore_ascii_case($alias))* {
+                        return Ok(Self::$variant);
+                    }
+                )*
+                Ok(Self::Custom(s.to_string()))
+            }
+        }
+
+        impl serde::Serialize for $name {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(self.as_str())
+            }
+        }
+
+        impl<'de> serde::Deserialize<'de> for $name {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let name = String::deserialize(deserializer)?;
+                Ok(name.parse().unwrap_or_else(|never| match never {}))
+            }
+        }
+    };
+}
+
+pub(crate) use model_names;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names_round_trip() {
+        for model in ChatModelName::ALL {
+            assert_eq!(model.to_string().parse::<ChatModelName>().unwrap(), *model);
+            let json = serde_json::to_string(model).unwrap();
+            assert_eq!(
+                serde_json::from_str::<ChatModelName>(&json).unwrap(),
+                *model
+            );
+            assert_eq!(model.info().unwrap().name(), model.as_str());
+        }
+        assert_eq!(ChatModelName::Glm4AirX.as_str(), "glm-4-airx");
+        assert_eq!(
+            "glm-4-flash-x".parse::<ChatModelName>().unwrap(),
+            ChatModelName::Glm4FlashX
+        );
+        assert_eq!(
+            "my-finetune".parse::<ChatModelName>().unwrap(),
+            ChatModelName::Custom("my-finetune".to_string())
+        );
+        assert!(model_info("my-finetune").is_none());
+        let video = model_info("cogvideox-3").unwrap();
+        assert!(video.supports(Feature::VisionInput));
+        assert!(!video.supports(Feature::Stream));
+        assert!(!model_info("cogview-4").unwrap().supports(Feature::Stream));
+    }
+}
@@ -0,0 +1,283 @@
+//! rebuild a complete response from a streamed chat completion
+use super::{data::*, response::*};
+use crate::{error::ZhipuApiError, role::Role};
+use futures::{Stream, StreamExt};
+use pin_project::pin_project;
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    pin::{Pin, pin},
+    task::{Context as TaskContext, Poll},
+};
+
+/// Folds streamed chunks or [`ChatStreamEvent`]s into the [`ChatApiResponse`] the same request
+/// would have returned without streaming. Events carry no id, creation time or model: push the
+/// chunks of [`ZhipuClient::chat_stream_chunks`](crate::client::ZhipuClient::chat_stream_chunks)
+/// for the response to have them.
+/// ```ignore
+/// let mut accumulator = ChatResponseAccumulator::new();
+/// while let Some(event) = stream.next().await {
+///     let event = event?;
+///     accumulator.push_event(&event);
+/// }
+/// let message = accumulator.message();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ChatResponseAccumulator {
+    id: Option<String>,
+    created: Option<u64>,
+    model: Option<String>,
+    content: String,
+    reasoning_content: String,
+    tool_calls: BTreeMap<u32, ToolCallParts>,
+    web_search: Option<Vec<WebSearchResponse>>,
+    usage: Option<Usage>,
+    finish_reason: Option<String>,
+    done: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+struct ToolCallParts {
+    id: Option<String>,
+    name: String,
+    arguments: String,
+}
+
+impl ChatResponseAccumulator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// add a raw chunk, keeping its id, creation time and model
+    pub fn push_chunk(&mut self, chunk: &ChatApiResponseStream) {
+        if let Some(id) = chunk.get_id() {
+            self.id.get_or_insert_with(|| id.to_string());
+        }
+        if let Some(created) = chunk.get_created() {
+            self.created.get_or_insert(created);
+        }
+        if let Some(model) = chunk.get_model() {
+            self.model.get_or_insert_with(|| model.to_string());
+        }
+        for event in chunk.events() {
+            self.push_event(&event);
+        }
+    }
+
+    /// add one event: text is appended, tool-call fragments are merged by `index`
+    pub fn push_event(&mut self, event: &ChatStreamEvent) {
+        match event {
+            ChatStreamEvent::ReasoningDelta(text) => self.reasoning_content.push_str(text),
+            ChatStreamEvent::ContentDelta(text) => self.content.push_str(text),
+            ChatStreamEvent::ToolCallDelta {
+                index,
+                id,
+                name,
+                arguments_fragment,
+            } => {
+                let call = self.tool_calls.entry(*index).or_default();
+                if let Some(id) = id {
+                    call.id = Some(id.clone());
+                }
+                if let Some(name) = name {
+                    call.name = name.clone();
+                }
+                call.arguments.push_str(arguments_fragment);
+            }
+            ChatStreamEvent::WebSearch(results) => {
+                self.web_search
+                    .get_or_insert_with(Vec::new)
+                    .extend(results.iter().cloned());
+            }
+            ChatStreamEvent::Usage(usage) => self.usage = Some(usage.clone()),
+            ChatStreamEvent::Finish { reason } => self.finish_reason = Some(reason.clone()),
+            ChatStreamEvent::Done => self.done = true,
+        }
+    }
+
+    /// the answer received so far
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+
+    /// the chain of thought received so far
+    pub fn reasoning_content(&self) -> &str {
+        &self.reasoning_content
+    }
+
+    /// the raw arguments of the tool call at `index` received so far
+    pub fn tool_call_arguments(&self, index: u32) -> Option<&str> {
+        self.tool_calls.get(&index).map(|c| c.arguments.as_str())
+    }
+
+    pub fn usage(&self) -> Option<&Usage> {
+        self.usage.as_ref()
+    }
+
+    pub fn finish_reason(&self) -> Option<&str> {
+        self.finish_reason.as_deref()
+    }
+
+    pub fn web_search(&self) -> Option<&[WebSearchResponse]> {
+        self.web_search.as_deref()
+    }
+
+    /// whether the `[DONE]` marker was received
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// The assistant message built from everything received so far.
+    pub fn message(&self) -> Message {
+        let tool_calls: Vec<ToolCall> = self
+            .tool_calls
+            .iter()
+            .map(|(index, call)| {
+                ToolCall::function_call(*index, call.id.clone(), &call.name, &call.arguments)
+            })
+            .collect();
+        let content = (!self.content.is_empty() || tool_calls.is_empty())
+            .then(|| Context::simple_context(&self.content));
+        Message::new(
+            Role::Assistant.as_str(),
+            content,
+            (!tool_calls.is_empty()).then_some(tool_calls),
+        )
+        .with_reasoning_content(
+            (!self.reasoning_content.is_empty()).then(|| self.reasoning_content.clone()),
+        )
+    }
+
+    /// The complete response built from everything received so far.
+    pub fn response(&self) -> ChatApiResponse {
+        ChatApiResponse::new(
+            self.id.clone(),
+            self.created,
+            self.model.clone(),
+            vec![Choice::new(
+                0,
+                self.finish_reason.as_deref().unwrap_or_default(),
+                self.message(),
+            )],
+            self.usage.clone(),
+            self.web_search.clone(),
+        )
+    }
+}
+
+/// Adapters for streams of [`ChatStreamEvent`]s, such as the one returned by
+/// [`response_context_stream`].
+pub trait ChatStreamExt: Stream<Item = Result<ChatStreamEvent, ZhipuApiError>> + Sized {
+    /// Pass every event through while accumulating them, see [`Accumulate::accumulator`].
+    fn accumulate(self) -> Accumulate<Self> {
+        Accumulate {
+            inner: self,
+            accumulator: ChatResponseAccumulator::new(),
+        }
+    }
+
+    /// Consume the stream and return the complete response, or the first error. Its id,
+    /// creation time and model are `None`, see [`ChatResponseAccumulator`].
+    fn collect_response(self) -> impl Future<Output = Result<ChatApiResponse, ZhipuApiError>> {
+        async move {
+            let mut stream = pin!(self);
+            let mut accumulator = ChatResponseAccumulator::new();
+            while let Some(event) = stream.next().await {
+                accumulator.push_event(&event?);
+            }
+            Ok(accumulator.response())
+        }
+    }
+}
+
+impl<S> ChatStreamExt for S where S: Stream<Item = Result<ChatStreamEvent, ZhipuApiError>> {}
+
+/// A stream that accumulates the events passing through it, see [`ChatStreamExt::accumulate`].
+/// ```ignore
+/// let mut stream = pin!(client.chat_stream(&request).await?.accumulate());
+/// while let Some(event) = stream.next().await {
+///     if let Some(text) = event?.content() {
+///         print!("{}", text);
+///     }
+/// }
+/// history.push(stream.accumulator().message());
+/// ```
+#[pin_project]
+pub struct Accumulate<S> {
+    #[pin]
+    inner: S,
+    accumulator: ChatResponseAccumulator,
+}
+
+impl<S> Accumulate<S> {
+    /// everything received so far
+    pub fn accumulator(&self) -> &ChatResponseAccumulator {
+        &self.accumulator
+    }
+
+    pub fn into_accumulator(self) -> ChatResponseAccumulator {
+        self.accumulator
+    }
+}
+
+impl<S> Stream for Accumulate<S>
+where
+    S: Stream<Item = Result<ChatStreamEvent, ZhipuApiError>>,
+{
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+        let item = this.inner.poll_next(cx);
+        if let Poll::Ready(Some(Ok(event))) = &item {
+            this.accumulator.push_event(event);
+        }
+        item
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_collect_response_merges_fragments() {
+        let usage: Usage =
+            serde_json::from_str(r#"{"prompt_tokens":3,"completion_tokens":5,"total_tokens":8}"#)
+                .unwrap();
+        let events = vec![
+            ChatStreamEvent::ReasoningDelta("think".to_string()),
+            ChatStreamEvent::ContentDelta("Hel".to_string()),
+            ChatStreamEvent::ContentDelta("lo".to_string()),
+            ChatStreamEvent::ToolCallDelta {
+                index: 0,
+                id: Some("call_1".to_string()),
+                name: Some("get_weather".to_string()),
+                arguments_fragment: r#"{"city":"#.to_string(),
+            },
+            ChatStreamEvent::ToolCallDelta {
+                index: 0,
+                id: None,
+                name: None,
+                arguments_fragment: r#""Beijing","days":3}"#.to_string(),
+            },
+            ChatStreamEvent::Finish {
+                reason: "tool_calls".to_string(),
+            },
+            ChatStreamEvent::Usage(usage.clone()),
+            ChatStreamEvent::Done,
+        ];
+        let response = futures::stream::iter(events.into_iter().map(Ok))
+            .collect_response()
+            .await
+            .unwrap();
+        assert_eq!(response.get_usage(), Some(&usage));
+        let choice = &response.get_choices().unwrap()[0];
+        assert_eq!(choice.finish_reason(), "tool_calls");
+        let message = serde_json::to_value(choice.message()).unwrap();
+        assert_eq!(message["content"], "Hello");
+        assert_eq!(message["reasoning_content"], "think");
+        assert_eq!(message["tool_calls"][0]["id"], "call_1");
+        assert_eq!(message["tool_calls"][0]["function"]["name"], "get_weather");
+    }
+}
@@ -1,8 +1,22 @@
-///! the chat AI api of zhipu
-use super::data::*;
-use serde::{Deserialize, Serialize};
+//! the chat AI api of zhipu
+use super::{data::*, model::ChatModelName, response::*, structured::parse_json};
+use crate::{
+    api_resource::capability::Feature,
+    api_resource::tokenizer::EstimateTokens,
+    client::ZhipuClient,
+    endpoint::{Endpoint, EndpointConfig},
+    error::ZhipuApiError,
+};
+use async_stream::stream;
+use futures::{Stream, StreamExt};
+use log::warn;
+use serde::{
+    Deserialize, Serialize,
+    de::{DeserializeOwned, Error as _},
+};
+use std::pin::pin;
 
-const API_URL: &str = "https://open.bigmodel.cn/api/paas/v4/chat/completions";
+pub(crate) const API_PATH: &str = "/chat/completions";
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[non_exhaustive]
@@ -47,16 +61,184 @@ pub struct ChatApiRequest {
     /// A list of tools that the model can use during the conversation.
     #[serde(skip_serializing_if = "Option::is_none")]
     tools: Option<Vec<Tool>>,
-    /// Specifies the tool to be used. This can be a specific tool name or "auto" to let the model decide.
+    /// Which tool the model may call, see [`ToolChoice`].
     #[serde(skip_serializing_if = "Option::is_none")]
-    tool_choice: Option<String>,
+    tool_choice: Option<ToolChoice>,
     #[serde(skip_serializing_if = "Option::is_none", rename = "extra")]
     code_context: Option<Extra>,
+    /// The format of the reply, `{"type": "json_object"}` for JSON mode.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<ResponseFormat>,
 }
 
 impl ChatApiRequest {
-    pub fn to_json(&self) -> String {
-        serde_json::to_string(self).unwrap()
+    pub fn to_json(&self) -> Result<String, ZhipuApiError> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// the model the request is sent to
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// the maximum number of tokens to generate, if one was set
+    pub fn max_tokens(&self) -> Option<u32> {
+        self.max_tokens
+    }
+
+    /// the request id, if one was set
+    pub fn request_id(&self) -> Option<&str> {
+        self.request_id.as_deref()
+    }
+
+    /// the messages of the prompt
+    pub fn messages(&self) -> &[Message] {
+        &self.messages
+    }
+
+    /// the tools offered to the model, if any
+    pub fn tools(&self) -> Option<&[Tool]> {
+        self.tools.as_deref()
+    }
+
+    /// Check the request against the [`ModelInfo`](crate::api_resource::capability::ModelInfo)
+    /// of its model: thinking, streaming, tools, web search, image/video/audio input and
+    /// `max_tokens`. Requests for custom models pass these checks. The function forced by
+    /// [`ToolChoice::Function`] must be one of the tools, whatever the model.
+    pub fn validate(&self) -> Result<(), ZhipuApiError> {
+        self.validate_tool_choice()?;
+        let Some(info) = self
+            .model
+            .parse::<ChatModelName>()
+            .ok()
+            .and_then(|m| m.info())
+        else {
+            return Ok(());
+        };
+        let mut required = Vec::new();
+        if self.thinking.as_ref().is_some_and(Thinking::is_enabled) {
+            required.push(Feature::Thinking);
+        }
+        if self.stream == Some(true) {
+            required.push(Feature::Stream);
+        }
+        for tool in self.tools.iter().flatten() {
+            match tool.tool_type() {
+                Some(ToolType::web_search) => required.push(Feature::WebSearch),
+                Some(_) => required.push(Feature::Tools),
+                None => {}
+            }
+        }
+        for message in &self.messages {
+            if let Some(Context::RichContents(contents)) = message.content() {
+                for content in contents {
+                    match content.get_type() {
+                        "image_url" => required.push(Feature::VisionInput),
+                        "video_url" => required.push(Feature::VideoInput),
+                        "input_audio" => required.push(Feature::AudioInput),
+                        _ => {}
+                    }
+                }
+            }
+        }
+        if let Some(feature) = required.into_iter().find(|f| !info.supports(*f)) {
+            return Err(ZhipuApiError::InvalidRequest(format!(
+                "{} does not support {}",
+                self.model, feature
+            )));
+        }
+        if let Some(max_tokens) = self.max_tokens
+            && max_tokens > info.max_output_tokens()
+        {
+            return Err(ZhipuApiError::InvalidRequest(format!(
+                "max_tokens {} exceeds the {} output tokens of {}",
+                max_tokens,
+                info.max_output_tokens(),
+                self.model
+            )));
+        }
+        Ok(())
+    }
+
+    /// A choice other than `auto` or `none` needs tools, a forced function needs to be one of them.
+    fn validate_tool_choice(&self) -> Result<(), ZhipuApiError> {
+        let tools = self.tools.as_deref().unwrap_or_default();
+        match &self.tool_choice {
+            Some(ToolChoice::Required) if tools.is_empty() => Err(ZhipuApiError::InvalidRequest(
+                "tool_choice is `required` but there are no tools".to_string(),
+            )),
+            Some(ToolChoice::Function(name))
+                if !tools
+                    .iter()
+                    .filter_map(Tool::get_function)
+                    .any(|function| function.name() == name) =>
+            {
+                Err(ZhipuApiError::InvalidRequest(format!(
+                    "tool_choice forces the function `{}` which is not among the tools",
+                    name
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// replace the messages, keeping every other setting
+    pub(crate) fn with_messages(mut self, messages: Vec<Message>) -> Self {
+        self.messages = messages;
+        self
+    }
+
+    /// ask for the whole response at once, whatever the builder said
+    pub(crate) fn without_stream(mut self) -> Self {
+        self.stream = Some(false);
+        self
+    }
+
+    /// offer `tools` on top of those already set
+    pub(crate) fn with_tools(mut self, tools: &[Tool]) -> Self {
+        if !tools.is_empty() {
+            self.tools
+                .get_or_insert_with(Vec::new)
+                .extend_from_slice(tools);
+        }
+        self
+    }
+
+    /// set a generated request id when none was provided, so retries share the same id
+    pub(crate) fn ensure_request_id(&mut self, generate: impl FnOnce() -> String) {
+        self.request_id.get_or_insert_with(generate);
+    }
+}
+
+impl Endpoint for ChatApiRequest {
+    type Response = ChatApiResponse;
+    const NAME: &'static str = "chat";
+
+    fn path(&self) -> String {
+        API_PATH.to_string()
+    }
+
+    fn body(&self) -> Result<Option<String>, ZhipuApiError> {
+        self.to_json().map(Some)
+    }
+
+    fn model(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
+    fn request_id(&self) -> Option<&str> {
+        self.request_id.as_deref()
+    }
+
+    /// the prompt size plus the requested completion size. The body is not counted: inline
+    /// images, videos and audio clips would weigh as much as their base64 text.
+    fn estimate_tokens(&self, _body: &str) -> u32 {
+        self.estimated_tokens()
+            .saturating_add(self.max_tokens.unwrap_or(0))
+    }
+
+    fn usage(response: &ChatApiResponse) -> Option<&Usage> {
+        response.get_usage()
     }
 }
 
@@ -91,9 +273,12 @@ pub struct ChatApiRequestBuilder {
     /// A list of tools that the model can use during the conversation.
     tools: Option<Vec<Tool>>,
     /// Specifies the tool to be used. This can be a specific tool name or "auto" to let the model decide.
-    tool_choice: Option<String>,
+    tool_choice: Option<ToolChoice>,
     /// codegeex model information.
     code_context: Option<Extra>,
+    response_format: Option<ResponseFormat>,
+    /// where the request is sent, [`EndpointConfig::global`] when `None`.
+    endpoint: Option<EndpointConfig>,
 }
 
 impl ChatApiRequestBuilder {
@@ -113,6 +298,8 @@ impl ChatApiRequestBuilder {
             tools: None,
             tool_choice: None,
             code_context: None,
+            response_format: None,
+            endpoint: None,
         }
     }
 
@@ -311,10 +498,15 @@ impl ChatApiRequestBuilder {
     }
 
     /// set tool_choice
-    /// default: None。
-    /// just support `auto`。Do not use it!
-    pub fn tool_choice(&mut self, tool_choice: &str) -> &mut Self {
-        self.tool_choice = Some(tool_choice.to_string());
+    /// default: None, which is [`ToolChoice::Auto`]。
+    /// A forced function must be one of the tools, see [`ChatApiRequest::validate`].
+    /// ```ignore
+    /// let mut builder = ChatApiRequestBuilder::new("glm-4")
+    ///                  .add_tools(Tool::new().function(function))
+    ///                  .tool_choice(ToolChoice::function("query_train_info"));
+    /// ```
+    pub fn tool_choice(&mut self, tool_choice: impl Into<ToolChoice>) -> &mut Self {
+        self.tool_choice = Some(tool_choice.into());
         self
     }
 
@@ -339,7 +531,7 @@ impl ChatApiRequestBuilder {
         self
     }
 
-    /// add messages, also see [`add_message`]
+    /// add messages, also see [`Self::add_message`]
     pub fn add_messages(&mut self, messages: Messages) -> &mut Self {
         self.messages.extend(messages.messages);
         self
@@ -352,9 +544,55 @@ impl ChatApiRequestBuilder {
         self
     }
 
+    /// set the format of the reply
+    /// default: None, which is free text
+    /// ```ignore
+    /// let mut builder = ChatApiRequestBuilder::new("glm-4-flash")
+    ///                  .response_format(ResponseFormat::JsonObject);
+    /// ```
+    pub fn response_format(&mut self, response_format: ResponseFormat) -> &mut Self {
+        self.response_format = Some(response_format);
+        self
+    }
+
+    /// ask for a JSON object, same as `response_format(ResponseFormat::JsonObject)`
+    pub fn json_mode(&mut self) -> &mut Self {
+        self.response_format(ResponseFormat::JsonObject)
+    }
+
+    /// set the endpoint the request is sent to
+    /// default: None。**when it is `None`, [`EndpointConfig::global`] is used**
+    /// ```ignore
+    /// let mut builder = ChatApiRequestBuilder::new("glm-4")
+    ///                  .endpoint(EndpointConfig::international());
+    /// ```
+    pub fn endpoint(&mut self, endpoint: EndpointConfig) -> &mut Self {
+        self.endpoint = Some(endpoint);
+        self
+    }
+
+    /// Build the request, logging a warning when the model does not support it,
+    /// see [`ChatApiRequest::validate`].
     pub fn build(&self) -> (String, ChatApiRequest) {
+        let (api_url, request) = self.build_unchecked();
+        if let Err(e) = request.validate() {
+            warn!("{}", e);
+        }
+        (api_url, request)
+    }
+
+    /// Build the request, failing when the model does not support it,
+    /// see [`ChatApiRequest::validate`].
+    pub fn try_build(&self) -> Result<(String, ChatApiRequest), ZhipuApiError> {
+        let (api_url, request) = self.build_unchecked();
+        request.validate()?;
+        Ok((api_url, request))
+    }
+
+    pub(crate) fn build_unchecked(&self) -> (String, ChatApiRequest) {
+        let endpoint = self.endpoint.clone().unwrap_or_else(EndpointConfig::global);
         (
-            API_URL.to_string(),
+            endpoint.url(API_PATH),
             ChatApiRequest {
                 model: self.model.clone(),
                 request_id: self.request_id.clone(),
@@ -370,7 +608,233 @@ impl ChatApiRequestBuilder {
                 tools: self.tools.clone(),
                 tool_choice: self.tool_choice.clone(),
                 code_context: self.code_context.clone(),
+                response_format: self.response_format,
             },
         )
     }
+
+    /// Build the request with `stream` disabled and send it through `client`.
+    /// The request goes to the endpoint of the client, [`Self::endpoint`] is ignored.
+    /// A request the model does not support fails without being sent, see [`Self::try_build`].
+    /// ```ignore
+    /// let response = ChatApiRequestBuilder::new("glm-4-flash")
+    ///     .add_message(chat_simple_message!(Role::User, "hello"))
+    ///     .send(&client)
+    ///     .await?;
+    /// ```
+    pub async fn send(&self, client: &ZhipuClient) -> Result<ChatApiResponse, ZhipuApiError> {
+        let (_, mut request) = self.try_build()?;
+        request.stream = Some(false);
+        client.chat(&request).await
+    }
+
+    /// Send the request in JSON mode and deserialize the reply into `T`, code fences and text
+    /// around the JSON ignored, see [`parse_json`].
+    /// ```ignore
+    /// #[derive(Deserialize)]
+    /// struct Translation { text: String, language: String }
+    /// let translation: Translation = ChatApiRequestBuilder::new("glm-4-flash")
+    ///     .add_message(Message::system("Translate to English, reply {\"text\", \"language\"}"))
+    ///     .add_message(Message::user("你好"))
+    ///     .send_json(&client)
+    ///     .await?;
+    /// ```
+    pub async fn send_json<T: DeserializeOwned>(
+        &self,
+        client: &ZhipuClient,
+    ) -> Result<T, ZhipuApiError> {
+        self.send_json_repairing(client, 1).await
+    }
+
+    /// [`Self::send_json`], asking the model to fix a reply that does not deserialize into `T`:
+    /// the reply and the error are appended to the messages and the request is sent again, up
+    /// to `attempts` requests in all. The error of the last attempt is returned.
+    pub async fn send_json_repairing<T: DeserializeOwned>(
+        &self,
+        client: &ZhipuClient,
+        attempts: usize,
+    ) -> Result<T, ZhipuApiError> {
+        let (_, mut request) = self.try_build()?;
+        request.stream = Some(false);
+        request.response_format = Some(ResponseFormat::JsonObject);
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let response = client.chat(&request).await?;
+            let message = response
+                .get_choices()
+                .and_then(|choices| choices.first())
+                .map(|choice| choice.message().clone())
+                .ok_or_else(|| serde_json::Error::custom("the response has no choices"))?;
+            let reply = message.content().map(|c| c.to_string()).unwrap_or_default();
+            match parse_json(&reply) {
+                Ok(value) => return Ok(value),
+                Err(e) if attempt >= attempts => return Err(e.into()),
+                Err(e) => {
+                    request.messages.push(message);
+                    request.messages.push(Message::user(format!(
+                        "Your reply could not be used: {}. Reply again with the corrected JSON only.",
+                        e
+                    )));
+                }
+            }
+        }
+    }
+
+    /// Build the request with `stream` enabled and stream its events through `client`.
+    /// A failure to start the call, including a request the model does not support, is yielded
+    /// as the only item of the stream.
+    /// ```ignore
+    /// let mut stream = pin!(ChatApiRequestBuilder::new("glm-4-flash")
+    ///     .add_message(chat_simple_message!(Role::User, "hello"))
+    ///     .stream(&client));
+    /// while let Some(event) = stream.next().await {
+    ///     if let Some(text) = event?.content() {
+    ///         print!("{}", text);
+    ///     }
+    /// }
+    /// ```
+    pub fn stream(
+        &self,
+        client: &ZhipuClient,
+    ) -> impl Stream<Item = Result<ChatStreamEvent, ZhipuApiError>> + 'static {
+        let request = self.try_build().map(|(_, mut request)| {
+            request.stream = Some(true);
+            request
+        });
+        let client = client.clone();
+        stream! {
+            let request = match request {
+                Ok(request) => request,
+                Err(e) => {
+                    yield Err(e);
+                    return;
+                }
+            };
+            let started = client.chat_stream(&request).await;
+            match started {
+                Ok(events) => {
+                    let mut events = pin!(events);
+                    while let Some(event) = events.next().await {
+                        yield event;
+                    }
+                }
+                Err(e) => yield Err(e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        api_resource::{
+            chat::Parameters,
+            tokenizer::{IMAGE_TOKENS, MESSAGE_OVERHEAD_TOKENS},
+        },
+        testing::MockServer,
+    };
+
+    #[tokio::test]
+    async fn test_send_and_stream_set_the_stream_flag() {
+        let server = MockServer::start().await.unwrap();
+        let client = server.client();
+        let mut builder = ChatApiRequestBuilder::new("glm-4-flash");
+        builder
+            .add_message(Message::new(
+                "user",
+                Some(Context::simple_context("hi")),
+                None,
+            ))
+            .stream_enable(true);
+
+        let response = builder.send(&client).await.unwrap();
+        assert_eq!(response.get_usage().unwrap().total_tokens(), 12);
+
+        builder.stream_enable(false);
+        let content: String = builder
+            .stream(&client)
+            .map(|e| e.unwrap().content().unwrap_or_default().to_string())
+            .collect()
+            .await;
+        assert_eq!(content, "Hello from mock");
+
+        let received = server.received();
+        assert_eq!(received[0].json()["stream"], false);
+        assert_eq!(received[1].json()["stream"], true);
+    }
+
+    #[test]
+    fn test_inline_images_count_as_images_for_the_rate_limiter() {
+        let mut png = vec![0x89, b'P', b'N', b'G'];
+        png.resize(1 << 20, 0);
+        let mut builder = ChatApiRequestBuilder::new("glm-4v-flash");
+        builder
+            .add_message(Message::user(Context::rich_contexts(
+                RichContent::image_bytes(&png, None).unwrap(),
+            )))
+            .max_tokens(100);
+        let (_, request) = builder.build();
+        let body = request.to_json().unwrap();
+        assert_eq!(
+            request.estimate_tokens(&body),
+            MESSAGE_OVERHEAD_TOKENS + IMAGE_TOKENS + 100
+        );
+    }
+
+    #[test]
+    fn test_validate_against_model_capabilities() {
+        let mut builder = ChatApiRequestBuilder::new("glm-4-flash");
+        builder.thinking_enable();
+        let error = builder.try_build().err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "ZhipuApiError: glm-4-flash does not support thinking"
+        );
+
+        builder.model_name("glm-4.6").max_tokens(1 << 20);
+        assert!(builder.try_build().is_err());
+
+        let mut builder = ChatApiRequestBuilder::new("glm-4-flash");
+        builder.add_message(Message::new(
+            "user",
+            Some(Context::rich_contexts(RichContent::image_url(
+                "https://a.b/c.png",
+            ))),
+            None,
+        ));
+        assert!(builder.try_build().is_err());
+        builder.model_name("glm-4v-flash");
+        assert!(builder.try_build().is_ok());
+        builder.model_name("my-finetune").thinking_enable();
+        assert!(builder.try_build().is_ok());
+    }
+
+    #[test]
+    fn test_forced_function_must_be_a_tool() {
+        let mut builder = ChatApiRequestBuilder::new("my-finetune");
+        builder.tool_choice(ToolChoice::function("query_train_info"));
+        assert!(builder.try_build().is_err());
+        builder.tool_choice("required");
+        assert!(builder.try_build().is_err());
+
+        builder.add_tools(Tool::new().function(Function::new(
+            "query_train_info",
+            "trains",
+            Parameters::default(),
+        )));
+        assert!(builder.try_build().is_ok());
+        builder.tool_choice(ToolChoice::function("query_train_info"));
+        let (_, request) = builder.try_build().unwrap();
+        let json: serde_json::Value = serde_json::from_str(&request.to_json().unwrap()).unwrap();
+        assert_eq!(
+            json["tool_choice"],
+            serde_json::json!({"type": "function", "function": {"name": "query_train_info"}})
+        );
+        assert_eq!(
+            serde_json::from_value::<ToolChoice>(json["tool_choice"].clone()).unwrap(),
+            ToolChoice::function("query_train_info")
+        );
+    }
 }
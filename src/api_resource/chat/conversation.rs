@@ -0,0 +1,282 @@
+//! multi-turn chat with the history kept for you
+use super::{api::*, data::*, response::*};
+use crate::{
+    api_resource::tokenizer::EstimateTokens, client::ZhipuClient, error::ZhipuApiError, role::Role,
+};
+use serde::de::Error as _;
+
+/// How much of the history is sent with each request. The system prompt is always sent, and
+/// the turns left out stay in [`Conversation::history`] until [`Conversation::trim`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HistoryLimit {
+    /// send the whole history
+    #[default]
+    Unlimited,
+    /// keep the last `n` turns, a turn starting at a user message
+    Turns(usize),
+    /// drop the oldest turns until the prompt fits in this many tokens, as estimated by
+    /// [`EstimateTokens`]; the latest turn is always kept
+    Tokens(u32),
+}
+
+/// A chat session that owns its system prompt and history: every reply of the model, tool calls
+/// and chain of thought included, is appended before the next turn.
+/// ```ignore
+/// let mut conversation = Conversation::new("glm-4-flash")
+///     .system_prompt("You are a translator")
+///     .history_limit(HistoryLimit::Turns(10));
+/// conversation.send(&client, "你好").await?;
+/// conversation.send(&client, "再见").await?;
+/// println!("{}", conversation.last().unwrap());
+/// ```
+pub struct Conversation {
+    /// the settings of every request, its messages are replaced by the conversation
+    request: ChatApiRequestBuilder,
+    system: Option<Message>,
+    history: Vec<Message>,
+    limit: HistoryLimit,
+}
+
+impl Conversation {
+    pub fn new(model: &str) -> Self {
+        Self::with_request(ChatApiRequestBuilder::new(model))
+    }
+
+    /// Start from a configured builder (tools, temperature, thinking...).
+    pub fn with_request(request: ChatApiRequestBuilder) -> Self {
+        Self {
+            request,
+            system: None,
+            history: Vec::new(),
+            limit: HistoryLimit::default(),
+        }
+    }
+
+    /// set the system prompt, sent first with every request
+    pub fn system_prompt(mut self, prompt: &str) -> Self {
+        self.system = Some(Message::system(prompt));
+        self
+    }
+
+    /// set how much history is sent, see [`HistoryLimit`]; the history itself is kept whole
+    pub fn history_limit(mut self, limit: HistoryLimit) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// the builder every request is made from
+    pub fn request_mut(&mut self) -> &mut ChatApiRequestBuilder {
+        &mut self.request
+    }
+
+    /// the whole history, without the system prompt
+    pub fn history(&self) -> &[Message] {
+        &self.history
+    }
+
+    /// the latest message of the history
+    pub fn last(&self) -> Option<&Message> {
+        self.history.last()
+    }
+
+    /// the messages of the next request: the system prompt and the history within the
+    /// [`HistoryLimit`]
+    pub fn messages(&self) -> Vec<Message> {
+        let history = &self.history[self.kept_from()..];
+        self.system.iter().chain(history).cloned().collect()
+    }
+
+    /// append a message, such as a tool result or a reply received by streaming
+    pub fn push(&mut self, message: Message) -> &mut Self {
+        self.history.push(message);
+        self
+    }
+
+    /// replace the history, e.g. to undo a failed run
+    pub(crate) fn set_history(&mut self, history: Vec<Message>) {
+        self.history = history;
+    }
+
+    /// forget the history, keeping the system prompt
+    pub fn clear(&mut self) {
+        self.history.clear();
+    }
+
+    /// Append a user message, send the conversation and append the reply of the model. The
+    /// user message is taken back when the call fails, so `send` can simply be called again.
+    pub async fn send(
+        &mut self,
+        client: &ZhipuClient,
+        text: &str,
+    ) -> Result<ChatApiResponse, ZhipuApiError> {
+        self.push(Message::user(text));
+        let result = self.complete(client).await;
+        if result.is_err() {
+            self.history.pop();
+        }
+        result
+    }
+
+    /// Send the conversation as it is and append the reply of the model, e.g. after pushing
+    /// the results of the tool calls it asked for.
+    pub async fn complete(
+        &mut self,
+        client: &ZhipuClient,
+    ) -> Result<ChatApiResponse, ZhipuApiError> {
+        self.complete_with_tools(client, &[]).await
+    }
+
+    /// [`Self::complete`] with `tools` offered on top of those of the request.
+    pub(crate) async fn complete_with_tools(
+        &mut self,
+        client: &ZhipuClient,
+        tools: &[Tool],
+    ) -> Result<ChatApiResponse, ZhipuApiError> {
+        let (_, request) = self.request.build_unchecked();
+        let request = request
+            .with_messages(self.messages())
+            .with_tools(tools)
+            .without_stream();
+        request.validate()?;
+        let response = client.chat(&request).await?;
+        let message = response
+            .get_choices()
+            .and_then(|choices| choices.first())
+            .map(|choice| choice.message().clone())
+            .ok_or_else(|| serde_json::Error::custom("the response has no choices"))?;
+        self.history.push(message);
+        Ok(response)
+    }
+
+    /// Discard the oldest turns the [`HistoryLimit`] leaves out of the requests, for good.
+    pub fn trim(&mut self) {
+        let keep_from = self.kept_from();
+        self.history.drain(..keep_from);
+    }
+
+    /// The index of the first message of the history sent, according to the [`HistoryLimit`].
+    fn kept_from(&self) -> usize {
+        let starts: Vec<usize> = self
+            .history
+            .iter()
+            .enumerate()
+            .filter(|(_, message)| message.role() == Role::User)
+            .map(|(index, _)| index)
+            .collect();
+        match self.limit {
+            HistoryLimit::Unlimited => 0,
+            HistoryLimit::Turns(turns) => match starts.len().checked_sub(turns.max(1)) {
+                Some(first) => starts[first],
+                None => 0,
+            },
+            HistoryLimit::Tokens(budget) => {
+                let mut total: u32 = self
+                    .system
+                    .iter()
+                    .chain(&self.history)
+                    .map(Message::estimated_tokens)
+                    .sum();
+                let mut keep_from = 0;
+                for &start in starts.iter().skip(1) {
+                    if total <= budget {
+                        break;
+                    }
+                    total -= self.history[keep_from..start]
+                        .iter()
+                        .map(Message::estimated_tokens)
+                        .sum::<u32>();
+                    keep_from = start;
+                }
+                keep_from
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{Mock, MockResponse, MockServer};
+
+    fn message(role: Role, text: &str) -> Message {
+        Message::new(role.as_str(), Some(Context::simple_context(text)), None)
+    }
+
+    fn conversation(limit: HistoryLimit) -> Conversation {
+        let mut conversation = Conversation::new("glm-4-flash")
+            .system_prompt("be brief")
+            .history_limit(limit);
+        for turn in ["one", "two", "three"] {
+            conversation
+                .push(message(Role::User, turn))
+                .push(message(Role::Assistant, &turn.repeat(40)));
+        }
+        conversation
+    }
+
+    #[test]
+    fn test_trim_keeps_whole_turns() {
+        let mut turns = conversation(HistoryLimit::Turns(2));
+        assert_eq!(turns.messages().len(), 5);
+        assert_eq!(turns.history().len(), 6);
+        turns.trim();
+        assert_eq!(turns.history().len(), 4);
+        assert_eq!(turns.history()[0].simple_context(), Some(("user", "two")));
+
+        let mut budget = conversation(HistoryLimit::Tokens(1));
+        budget.trim();
+        assert_eq!(budget.history().len(), 2);
+        assert_eq!(budget.messages()[0].role(), Role::System);
+        assert_eq!(
+            budget.messages()[1].simple_context(),
+            Some(("user", "three"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_send_appends_the_reply() {
+        let server = MockServer::start().await.unwrap();
+        let client = server.client();
+        let mut conversation = Conversation::new("glm-4-flash").system_prompt("be brief");
+        conversation.send(&client, "hi").await.unwrap();
+        conversation.send(&client, "again").await.unwrap();
+
+        assert_eq!(conversation.history().len(), 4);
+        assert_eq!(conversation.last().unwrap().role(), Role::Assistant);
+        let sent = server.received()[1].json();
+        assert_eq!(sent["messages"].as_array().unwrap().len(), 4);
+        assert_eq!(sent["messages"][0]["role"], "system");
+        assert_eq!(sent["messages"][3]["content"], "again");
+    }
+
+    #[tokio::test]
+    async fn test_send_keeps_the_settings_of_the_builder() {
+        let server = MockServer::start().await.unwrap();
+        let mut conversation = Conversation::new("glm-4-flash");
+        conversation.request_mut().stream_enable(true);
+        conversation.send(&server.client(), "hi").await.unwrap();
+
+        assert_eq!(server.received()[0].json()["stream"], false);
+        let (_, request) = conversation.request_mut().build_unchecked();
+        let json: serde_json::Value = serde_json::from_str(&request.to_json().unwrap()).unwrap();
+        assert_eq!(json["stream"], true);
+    }
+
+    #[tokio::test]
+    async fn test_failed_send_leaves_the_history_unchanged() {
+        let server = MockServer::start().await.unwrap();
+        server.mock(Mock::post(
+            "/chat/completions",
+            MockResponse::error(400, "1214", "invalid request"),
+        ));
+        let client = server.client();
+        let mut conversation = Conversation::new("glm-4-flash");
+        assert!(conversation.send(&client, "hi").await.is_err());
+        assert!(conversation.history().is_empty());
+
+        conversation.send(&client, "hi").await.unwrap();
+        assert_eq!(conversation.history().len(), 2);
+        let sent = server.received()[1].json();
+        assert_eq!(sent["messages"].as_array().unwrap().len(), 1);
+    }
+}
@@ -1,16 +1,24 @@
 //! chat model data structure
-use serde::{Deserialize, Deserializer, Serialize};
+use super::{
+    media::{MAX_IMAGE_BYTES, data_uri, media_type},
+    schema::{ArgumentsError, Parameters},
+};
+use crate::{error::ZhipuApiError, role::Role};
+use base64::prelude::*;
+use log::warn;
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::DeserializeOwned};
+use serde_json::Value;
 use std::{
     any::{Any, TypeId},
-    collections::HashMap,
-    fmt,
+    fmt, fs,
+    path::Path,
 };
 
 /// The context of the current conversation
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Message {
     /// Current conversation role, default is 'assistant' (model)
-    role: String,
+    role: Role,
     /// Current conversation content. It is null when a function is hit, otherwise, it returns the model reasoning result.
     #[serde(skip_serializing_if = "Option::is_none")]
     content: Option<Context>,
@@ -20,6 +28,9 @@ pub struct Message {
     /// Function names and parameters generated by the model to be called.
     #[serde(skip_serializing_if = "Option::is_none")]
     tool_calls: Option<Vec<ToolCall>>,
+    /// The id of the tool call a `tool` message answers.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_call_id: Option<String>,
 }
 
 impl fmt::Display for Message {
@@ -51,19 +62,67 @@ impl fmt::Display for Message {
 impl Message {
     pub fn new(role: &str, content: Option<Context>, tool_calls: Option<Vec<ToolCall>>) -> Self {
         Self {
-            role: role.to_string(),
+            role: Role::from(role),
             content,
             reasoning_content: None,
             tool_calls,
+            tool_call_id: None,
+        }
+    }
+    /// A system message
+    pub fn system(content: impl Into<Context>) -> Self {
+        Self::new(Role::System.as_str(), Some(content.into()), None)
+    }
+    /// A user message
+    pub fn user(content: impl Into<Context>) -> Self {
+        Self::new(Role::User.as_str(), Some(content.into()), None)
+    }
+    /// An assistant message, e.g. an earlier reply of the model
+    pub fn assistant(content: impl Into<Context>) -> Self {
+        Self::new(Role::Assistant.as_str(), Some(content.into()), None)
+    }
+    /// The result of the tool call `tool_call_id`, sent back to the model. An empty id is not
+    /// sent, and the API rejects the message.
+    pub fn tool_result(tool_call_id: &str, content: impl Into<Context>) -> Self {
+        if tool_call_id.is_empty() {
+            warn!("a tool result without a tool call id will be rejected by the API");
         }
+        Self {
+            tool_call_id: (!tool_call_id.is_empty()).then(|| tool_call_id.to_string()),
+            ..Self::new(Role::Tool.as_str(), Some(content.into()), None)
+        }
+    }
+    /// Get the role of the message
+    pub fn role(&self) -> Role {
+        self.role.clone()
+    }
+    /// Get the id of the tool call a `tool` message answers
+    pub fn tool_call_id(&self) -> Option<&str> {
+        self.tool_call_id.as_deref()
+    }
+    /// Get the content of the message
+    pub fn content(&self) -> Option<&Context> {
+        self.content.as_ref()
+    }
+    /// Get the tool calls requested by the model
+    pub fn tool_calls(&self) -> Option<&[ToolCall]> {
+        self.tool_calls.as_deref()
     }
     /// Get the content of the current conversation
     pub fn simple_context(&self) -> Option<(&str, &str)> {
         match &self.content {
-            Some(Context::SimpleContexts(content)) => Some((&self.role, content)),
+            Some(Context::SimpleContexts(content)) => Some((self.role.as_str(), content)),
             _ => None,
         }
     }
+    /// Get the content of the thought chain
+    pub fn reasoning_content(&self) -> Option<&str> {
+        self.reasoning_content.as_deref()
+    }
+    pub(crate) fn with_reasoning_content(mut self, reasoning_content: Option<String>) -> Self {
+        self.reasoning_content = reasoning_content;
+        self
+    }
 }
 
 /// making generate Messages essay
@@ -72,6 +131,12 @@ pub struct Messages {
     pub messages: Vec<Message>,
 }
 
+impl Default for Messages {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Messages {
     /// Create a new Messages with an empty list of messages
     pub fn new() -> Self {
@@ -82,20 +147,24 @@ impl Messages {
         self.messages.push(message);
         self
     }
-    /// Get the content of the current conversation
-    pub fn to_string(&self) -> String {
-        self.messages
-            .iter()
-            .map(|msg| msg.to_string())
-            .collect::<Vec<String>>()
-            .join("\n")
-    }
     /// Clear messages
     pub fn clear(&mut self) {
         self.messages.clear();
     }
 }
 
+impl fmt::Display for Messages {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let content = self
+            .messages
+            .iter()
+            .map(|msg| msg.to_string())
+            .collect::<Vec<String>>()
+            .join("\n");
+        write!(f, "{}", content)
+    }
+}
+
 /// The context of the current conversation
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(untagged)]
@@ -125,6 +194,24 @@ impl Context {
     }
 }
 
+impl From<&str> for Context {
+    fn from(content: &str) -> Self {
+        Context::simple_context(content)
+    }
+}
+
+impl From<String> for Context {
+    fn from(content: String) -> Self {
+        Context::SimpleContexts(content)
+    }
+}
+
+impl From<RichContent> for Context {
+    fn from(content: RichContent) -> Self {
+        Context::rich_contexts(content)
+    }
+}
+
 impl fmt::Display for Context {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -210,18 +297,86 @@ impl RichContent {
         }
     }
 
+    /// the type of the content: `text`, `image_url`, `video_url` or `input_audio`
+    pub fn get_type(&self) -> &str {
+        &self.item_type
+    }
+
+    /// the text, `None` for other types of content
+    pub fn get_text(&self) -> Option<&str> {
+        self.text.as_deref()
+    }
+
+    /// An audio clip in WAV format, already encoded in base64
     pub fn audio(data: &str) -> Self {
+        Self::input_audio(data.to_string(), "wav")
+    }
+
+    /// A WAV audio clip, rejected when the bytes are not WAV
+    pub fn audio_wav(bytes: &[u8]) -> Result<Self, ZhipuApiError> {
+        Self::audio_bytes(bytes, "audio/wav", "wav")
+    }
+
+    /// An MP3 audio clip, rejected when the bytes are not MP3
+    pub fn audio_mp3(bytes: &[u8]) -> Result<Self, ZhipuApiError> {
+        Self::audio_bytes(bytes, "audio/mpeg", "mp3")
+    }
+
+    fn audio_bytes(bytes: &[u8], expected: &str, format: &str) -> Result<Self, ZhipuApiError> {
+        let mime = media_type(bytes, None, "audio")?;
+        if mime != expected {
+            return Err(ZhipuApiError::InvalidRequest(format!(
+                "expected {}, got {}",
+                expected, mime
+            )));
+        }
+        Ok(Self::input_audio(BASE64_STANDARD.encode(bytes), format))
+    }
+
+    fn input_audio(data: String, format: &str) -> Self {
         Self {
             item_type: "input_audio".to_string(),
             video_url: None,
             image_url: None,
             input_audio: Some(Audio {
-                data: data.to_string(),
-                format: "awv".to_string(),
+                data,
+                format: format.to_string(),
             }),
             text: None,
         }
     }
+
+    /// An image sent inline as a `data:` URI. Its type is sniffed from the bytes when `mime` is
+    /// `None`; anything but an image is rejected. See [`Self::image_fitted`] (`image` feature)
+    /// to meet the size limits of the platform.
+    pub fn image_bytes(bytes: &[u8], mime: Option<&str>) -> Result<Self, ZhipuApiError> {
+        let mime = media_type(bytes, mime, "image")?;
+        if bytes.len() > MAX_IMAGE_BYTES {
+            warn!(
+                "the image has {} bytes, more than the {} the platform accepts",
+                bytes.len(),
+                MAX_IMAGE_BYTES
+            );
+        }
+        Ok(Self::image_url(&data_uri(bytes, &mime)))
+    }
+
+    /// An image file sent inline, see [`Self::image_bytes`]. The file is read synchronously.
+    pub fn image_file(path: impl AsRef<Path>) -> Result<Self, ZhipuApiError> {
+        Self::image_bytes(&fs::read(path)?, None)
+    }
+
+    /// A video sent inline as a `data:` URI, its type sniffed from the bytes when `mime` is
+    /// `None`.
+    pub fn video_bytes(bytes: &[u8], mime: Option<&str>) -> Result<Self, ZhipuApiError> {
+        let mime = media_type(bytes, mime, "video")?;
+        Ok(Self::video_url(&data_uri(bytes, &mime)))
+    }
+
+    /// A video file sent inline, see [`Self::video_bytes`]. The file is read synchronously.
+    pub fn video_file(path: impl AsRef<Path>) -> Result<Self, ZhipuApiError> {
+        Self::video_bytes(&fs::read(path)?, None)
+    }
 }
 
 impl fmt::Display for RichContent {
@@ -250,6 +405,12 @@ pub struct Tool {
     web_browser: Option<WebBrowser>,
 }
 
+impl Default for Tool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Tool {
     pub fn new() -> Self {
         Self {
@@ -304,6 +465,16 @@ impl Tool {
         self
     }
 
+    /// the kind of tool, `None` until one is set
+    pub fn tool_type(&self) -> Option<&ToolType> {
+        self.tool_type.as_ref()
+    }
+
+    /// the function of a function tool
+    pub fn get_function(&self) -> Option<&Function> {
+        self.function.as_ref()
+    }
+
     /// check tool type and modify `type` of tool
     fn check_type<T: Any>(&mut self, _value: &T) {
         let type_id = TypeId::of::<T>();
@@ -324,7 +495,7 @@ impl Tool {
 }
 
 /// tools type
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 #[allow(non_camel_case_types)]
 pub enum ToolType {
     function,
@@ -335,6 +506,73 @@ pub enum ToolType {
     web_browser,
 }
 
+/// Which tool the model may call.
+/// ```ignore
+/// let mut builder = ChatApiRequestBuilder::new("glm-4-flash");
+/// builder
+///     .add_tools(Tool::new().function(query_train_info))
+///     .tool_choice(ToolChoice::function("query_train_info"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum ToolChoice {
+    /// the model decides, the default
+    #[default]
+    Auto,
+    /// the model answers without calling a tool
+    None,
+    /// the model must call one of the tools
+    Required,
+    /// the model must call this function
+    Function(String),
+}
+
+impl ToolChoice {
+    /// force the function `name`
+    pub fn function(name: &str) -> Self {
+        Self::Function(name.to_string())
+    }
+}
+
+impl From<&str> for ToolChoice {
+    /// `auto`, `none` and `required`, anything else being the name of a function
+    fn from(value: &str) -> Self {
+        match value {
+            "auto" => Self::Auto,
+            "none" => Self::None,
+            "required" => Self::Required,
+            name => Self::function(name),
+        }
+    }
+}
+
+impl Serialize for ToolChoice {
+    /// a string, or `{"type": "function", "function": {"name": ...}}` for a function
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Auto => serializer.serialize_str("auto"),
+            Self::None => serializer.serialize_str("none"),
+            Self::Required => serializer.serialize_str("required"),
+            Self::Function(name) => serde_json::json!({
+                "type": "function",
+                "function": {"name": name}
+            })
+            .serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ToolChoice {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match Value::deserialize(deserializer)? {
+            Value::String(choice) => Ok(Self::from(choice.as_str())),
+            choice => choice["function"]["name"]
+                .as_str()
+                .map(Self::function)
+                .ok_or_else(|| serde::de::Error::custom("a tool choice names no function")),
+        }
+    }
+}
+
 /// Tool response
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ToolCall {
@@ -346,6 +584,44 @@ pub struct ToolCall {
     index: Option<u32>,
 }
 
+impl ToolCall {
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+    /// the function to call, `None` for the built-in tools
+    pub fn function(&self) -> Option<&FunctionRespond> {
+        self.function.as_ref()
+    }
+    /// Deserialize the arguments of the function call into `T`, see
+    /// [`FunctionRespond::parse_args`]. Fails for the built-in tools, which have no function.
+    pub fn parse_args<T: DeserializeOwned>(&self) -> Result<T, serde_json::Error> {
+        match &self.function {
+            Some(function) => function.parse_args(),
+            None => Err(serde::de::Error::custom(
+                "the tool call is not a function call",
+            )),
+        }
+    }
+    /// A function call rebuilt from streamed fragments.
+    pub(crate) fn function_call(
+        index: u32,
+        id: Option<String>,
+        name: &str,
+        arguments: &str,
+    ) -> Self {
+        Self {
+            id,
+            call_type: Some("function".to_string()),
+            function: Some(FunctionRespond {
+                name: name.to_string(),
+                arguments: parse_arguments(arguments),
+            }),
+            drawing_tool: None,
+            index: Some(index),
+        }
+    }
+}
+
 impl fmt::Display for ToolCall {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut output = String::new();
@@ -372,27 +648,79 @@ impl fmt::Display for ToolCall {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct FunctionRespond {
     name: String,
-    #[serde(deserialize_with = "deserialize_arguments")]
-    arguments: HashMap<String, String>, // 直接使用 HashMap 来解析 arguments 字段
+    /// Sent as a JSON string, as the platform expects when the call is echoed back
+    #[serde(
+        serialize_with = "serialize_arguments",
+        deserialize_with = "deserialize_arguments"
+    )]
+    arguments: Value,
+}
+impl FunctionRespond {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    /// The arguments chosen by the model, usually an object. Text that is not valid JSON is kept
+    /// as a string.
+    pub fn arguments(&self) -> &Value {
+        &self.arguments
+    }
+    /// Deserialize the arguments into `T`.
+    /// ```ignore
+    /// #[derive(Deserialize)]
+    /// struct Weather { city: String, days: u32 }
+    /// let weather: Weather = function.parse_args()?;
+    /// ```
+    pub fn parse_args<T: DeserializeOwned>(&self) -> Result<T, serde_json::Error> {
+        T::deserialize(&self.arguments)
+    }
+    /// Check the arguments against the declared schema of the function, see
+    /// [`Parameters::check`].
+    pub fn validate(&self, parameters: &Parameters) -> Result<(), ArgumentsError> {
+        parameters.check(&self.arguments)
+    }
 }
 impl fmt::Display for FunctionRespond {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "Function Name: {}\nArguments:", self.name)?;
-        for (key, value) in &self.arguments {
-            write!(f, "\n  {}: {}", key, value)?;
+        match &self.arguments {
+            Value::Object(arguments) => {
+                for (key, value) in arguments {
+                    match value {
+                        Value::String(value) => write!(f, "\n  {}: {}", key, value)?,
+                        value => write!(f, "\n  {}: {}", key, value)?,
+                    }
+                }
+            }
+            arguments => write!(f, " {}", arguments)?,
         }
 
         Ok(())
     }
 }
-fn deserialize_arguments<'de, D>(deserializer: D) -> Result<HashMap<String, String>, D::Error>
+/// The arguments as sent by the platform: JSON text, or a string that is not valid JSON.
+fn parse_arguments(arguments: &str) -> Value {
+    if arguments.trim().is_empty() {
+        return Value::Object(Default::default());
+    }
+    serde_json::from_str(arguments).unwrap_or_else(|_| Value::String(arguments.to_string()))
+}
+fn serialize_arguments<S>(arguments: &Value, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match arguments {
+        Value::String(raw) => serializer.serialize_str(raw),
+        arguments => serializer.serialize_str(&arguments.to_string()),
+    }
+}
+fn deserialize_arguments<'de, D>(deserializer: D) -> Result<Value, D::Error>
 where
     D: Deserializer<'de>,
 {
-    let s: String = Deserialize::deserialize(deserializer)?;
-    let map: HashMap<String, String> =
-        serde_json::from_str(&s).map_err(serde::de::Error::custom)?;
-    Ok(map)
+    Ok(match Value::deserialize(deserializer)? {
+        Value::String(raw) => parse_arguments(&raw),
+        arguments => arguments,
+    })
 }
 
 /// function call data object
@@ -418,42 +746,14 @@ impl Function {
             parameters,
         }
     }
-}
-
-/// function call parameters object
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct Parameters {
-    #[serde(rename = "type")]
-    param_type: String,
-    properties: HashMap<String, Property>,
-    required: Option<Vec<String>>,
-}
-
-impl Parameters {
-    pub fn new(properties: HashMap<String, Property>) -> Self {
-        let required = properties.keys().map(|k| k.to_string()).collect();
-        Self {
-            param_type: "object".to_string(),
-            properties,
-            required: Some(required),
-        }
+    pub fn name(&self) -> &str {
+        &self.name
     }
-}
-
-/// function call children object
-#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
-pub struct Property {
-    #[serde(rename = "type")]
-    prop_type: String,
-    description: String,
-}
-
-impl Property {
-    pub fn new(prop_type: &str, description: &str) -> Property {
-        Property {
-            prop_type: prop_type.to_string(),
-            description: description.to_string(),
-        }
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+    pub fn parameters(&self) -> &Parameters {
+        &self.parameters
     }
 }
 
@@ -487,6 +787,12 @@ pub struct WebSearch {
     search_prompt: Option<String>,
 }
 
+impl Default for WebSearch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl WebSearch {
     pub fn new() -> Self {
         Self {
@@ -626,13 +932,23 @@ pub struct Choice {
 }
 
 impl Choice {
+    pub(crate) fn new(index: u32, finish_reason: &str, message: Message) -> Self {
+        Self {
+            finish_reason: finish_reason.to_string(),
+            index,
+            message,
+        }
+    }
     pub fn message(&self) -> &Message {
         &self.message
     }
+    pub fn finish_reason(&self) -> &str {
+        &self.finish_reason
+    }
 }
 
 /// tokens usage object
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Usage {
     /// Number of tokens input by the user
     prompt_tokens: u32,
@@ -642,8 +958,20 @@ pub struct Usage {
     total_tokens: u32,
 }
 
+impl Usage {
+    pub fn prompt_tokens(&self) -> u32 {
+        self.prompt_tokens
+    }
+    pub fn completion_tokens(&self) -> u32 {
+        self.completion_tokens
+    }
+    pub fn total_tokens(&self) -> u32 {
+        self.total_tokens
+    }
+}
+
 /// web search response object
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct WebSearchResponse {
     content: Option<String>,
     icon: Option<String>,
@@ -765,6 +1093,17 @@ impl CodeContext {
     }
 }
 
+/// The format of the reply of the model
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseFormat {
+    /// free text, the default
+    #[default]
+    Text,
+    /// JSON mode: the reply is a JSON object
+    JsonObject,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Thinking {
     #[serde(rename = "type")]
@@ -791,4 +1130,8 @@ impl Thinking {
             thinking: "disabled".to_string(),
         }
     }
+
+    pub fn is_enabled(&self) -> bool {
+        self.thinking == "enabled"
+    }
 }
@@ -1,9 +1,9 @@
-///! System Message and User Message
+//! System Message and User Message
 #[macro_export]
 macro_rules! chat_simple_message {
     ($role:expr, $content:expr) => {
         Message::new(
-            $role.into(),
+            AsRef::<str>::as_ref(&$role),
             Some(Context::SimpleContexts($content.to_string())),
             None,
         )
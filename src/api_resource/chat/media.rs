@@ -0,0 +1,206 @@
+//! images, videos and audio clips sent inline with the messages
+#[cfg(feature = "image")]
+use super::data::RichContent;
+use crate::error::ZhipuApiError;
+use base64::prelude::*;
+
+/// the largest image the platform accepts, in bytes
+pub const MAX_IMAGE_BYTES: usize = 5 * 1024 * 1024;
+/// the longest side of an image the platform accepts, in pixels
+pub const MAX_IMAGE_SIDE: u32 = 6000;
+
+/// The MIME type of an image, video or audio clip, recognized from its first bytes: PNG, JPEG,
+/// GIF, WebP, BMP, MP4, QuickTime, WebM, AVI, WAV and MP3.
+pub fn sniff_mime(bytes: &[u8]) -> Option<&'static str> {
+    let riff = |kind: &[u8]| bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(kind);
+    Some(match bytes {
+        [0x89, b'P', b'N', b'G', ..] => "image/png",
+        [0xFF, 0xD8, 0xFF, ..] => "image/jpeg",
+        [b'G', b'I', b'F', b'8', ..] => "image/gif",
+        [b'B', b'M', ..] => "image/bmp",
+        [0x1A, 0x45, 0xDF, 0xA3, ..] => "video/webm",
+        [b'I', b'D', b'3', ..] => "audio/mpeg",
+        [0xFF, frame, ..] if frame & 0xE0 == 0xE0 => "audio/mpeg",
+        _ if riff(b"WEBP") => "image/webp",
+        _ if riff(b"AVI ") => "video/x-msvideo",
+        _ if riff(b"WAVE") => "audio/wav",
+        _ if bytes.get(4..8) == Some(b"ftyp") => match bytes.get(8..12) {
+            Some(b"qt  ") => "video/quicktime",
+            _ => "video/mp4",
+        },
+        _ => return None,
+    })
+}
+
+/// `bytes` as a `data:` URI of type `mime`.
+pub(crate) fn data_uri(bytes: &[u8], mime: &str) -> String {
+    format!("data:{};base64,{}", mime, BASE64_STANDARD.encode(bytes))
+}
+
+/// The MIME type of `bytes`, checked to be of the `kind` (`image`, `video`...) expected.
+pub(crate) fn media_type(
+    bytes: &[u8],
+    mime: Option<&str>,
+    kind: &str,
+) -> Result<String, ZhipuApiError> {
+    let mime = match mime {
+        Some(mime) => mime,
+        None => sniff_mime(bytes).ok_or_else(|| {
+            ZhipuApiError::InvalidRequest(format!("the {} format is not recognized", kind))
+        })?,
+    };
+    if mime.split('/').next() != Some(kind) {
+        return Err(ZhipuApiError::InvalidRequest(format!(
+            "expected a {}/* type, got {}",
+            kind, mime
+        )));
+    }
+    Ok(mime.to_string())
+}
+
+/// How large an image may be, see [`RichContent::image_fitted`].
+#[cfg(feature = "image")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageLimits {
+    /// the size of the encoded image, in bytes
+    pub max_bytes: usize,
+    /// the longest side, in pixels
+    pub max_side: u32,
+}
+
+#[cfg(feature = "image")]
+impl Default for ImageLimits {
+    /// the limits of the platform
+    fn default() -> Self {
+        Self {
+            max_bytes: MAX_IMAGE_BYTES,
+            max_side: MAX_IMAGE_SIDE,
+        }
+    }
+}
+
+#[cfg(feature = "image")]
+impl RichContent {
+    /// An image made to fit `limits`: sent as it is when it is a PNG or JPEG that already fits,
+    /// otherwise scaled down to `max_side` and encoded as JPEG at the highest quality within
+    /// `max_bytes`.
+    /// ```ignore
+    /// let photo = std::fs::read("photo.heic.jpg")?;
+    /// let content = RichContent::image_fitted(&photo, ImageLimits::default())?;
+    /// ```
+    pub fn image_fitted(bytes: &[u8], limits: ImageLimits) -> Result<Self, ZhipuApiError> {
+        use image::{ImageReader, codecs::jpeg::JpegEncoder, imageops::FilterType};
+
+        let invalid = |e: image::ImageError| ZhipuApiError::InvalidRequest(e.to_string());
+        let image = ImageReader::new(std::io::Cursor::new(bytes))
+            .with_guessed_format()?
+            .decode()
+            .map_err(invalid)?;
+        let side = image.width().max(image.height());
+        // the platform documents only PNG and JPEG for image input
+        let known = matches!(sniff_mime(bytes), Some("image/png" | "image/jpeg"));
+        if known && side <= limits.max_side && bytes.len() <= limits.max_bytes {
+            return Self::image_bytes(bytes, None);
+        }
+        let image = if side > limits.max_side {
+            image.resize(limits.max_side, limits.max_side, FilterType::Triangle)
+        } else {
+            image
+        };
+        let image = image.to_rgb8();
+        let mut encoded = Vec::new();
+        for quality in [90, 80, 70, 60, 50, 40, 30] {
+            encoded.clear();
+            image
+                .write_with_encoder(JpegEncoder::new_with_quality(&mut encoded, quality))
+                .map_err(invalid)?;
+            if encoded.len() <= limits.max_bytes {
+                return Self::image_bytes(&encoded, Some("image/jpeg"));
+            }
+        }
+        Err(ZhipuApiError::InvalidRequest(format!(
+            "the image does not fit in {} bytes",
+            limits.max_bytes
+        )))
+    }
+
+    /// [`Self::image_fitted`] for an image file.
+    pub fn image_file_fitted(
+        path: impl AsRef<std::path::Path>,
+        limits: ImageLimits,
+    ) -> Result<Self, ZhipuApiError> {
+        Self::image_fitted(&std::fs::read(path)?, limits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_resource::chat::RichContent;
+
+    #[test]
+    fn test_inline_media() {
+        let png = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+        let image = serde_json::to_value(RichContent::image_bytes(&png, None).unwrap()).unwrap();
+        assert_eq!(
+            image["image_url"]["url"],
+            format!("data:image/png;base64,{}", BASE64_STANDARD.encode(png))
+        );
+        assert!(RichContent::image_bytes(b"RIFF\0\0\0\0WAVE", None).is_err());
+        assert_eq!(sniff_mime(b"\0\0\0\x18ftypmp42"), Some("video/mp4"));
+
+        assert!(RichContent::audio_wav(b"ID3\x04").is_err());
+        assert!(RichContent::audio_mp3(b"not audio").is_err());
+        let audio = serde_json::to_value(RichContent::audio_mp3(b"ID3\x04").unwrap()).unwrap();
+        assert_eq!(audio["input_audio"]["format"], "mp3");
+        assert_eq!(
+            audio["input_audio"]["data"],
+            BASE64_STANDARD.encode(b"ID3\x04")
+        );
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn test_image_fitted() {
+        let mut png = Vec::new();
+        image::RgbImage::from_pixel(64, 32, image::Rgb([200, 30, 30]))
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        let limits = ImageLimits {
+            max_bytes: MAX_IMAGE_BYTES,
+            max_side: 16,
+        };
+        let content =
+            serde_json::to_value(RichContent::image_fitted(&png, limits).unwrap()).unwrap();
+        let url = content["image_url"]["url"].as_str().unwrap();
+        let jpeg = BASE64_STANDARD
+            .decode(url.strip_prefix("data:image/jpeg;base64,").unwrap())
+            .unwrap();
+        let fitted = image::load_from_memory(&jpeg).unwrap();
+        assert_eq!((fitted.width(), fitted.height()), (16, 8));
+
+        // small enough, but in formats that are only sent after conversion
+        for format in [image::ImageFormat::Pnm, image::ImageFormat::Bmp] {
+            let mut encoded = Vec::new();
+            image::RgbImage::from_pixel(4, 4, image::Rgb([0, 0, 255]))
+                .write_to(&mut std::io::Cursor::new(&mut encoded), format)
+                .unwrap();
+            let content = RichContent::image_fitted(&encoded, ImageLimits::default()).unwrap();
+            let content = serde_json::to_value(content).unwrap();
+            assert!(
+                content["image_url"]["url"]
+                    .as_str()
+                    .unwrap()
+                    .starts_with("data:image/jpeg;base64,")
+            );
+        }
+        let content = RichContent::image_fitted(&png, ImageLimits::default()).unwrap();
+        let content = serde_json::to_value(content).unwrap();
+        assert!(
+            content["image_url"]["url"]
+                .as_str()
+                .unwrap()
+                .starts_with("data:image/png;base64,")
+        );
+    }
+}
@@ -1,13 +1,25 @@
+pub mod accumulator;
 pub mod api;
+pub mod conversation;
 pub mod data;
 pub mod iner_macro;
+pub mod media;
 pub mod model;
 pub mod response;
+pub mod schema;
+pub mod structured;
+pub mod tools;
 
+pub use accumulator::*;
 pub use api::*;
+pub use conversation::*;
 pub use data::*;
+pub use media::*;
 pub use model::*;
 pub use response::*;
+pub use schema::*;
+pub use structured::*;
+pub use tools::*;
 
 use super::builder::Builder;
 
@@ -1,119 +1,95 @@
 //! # glm4 model name
-use std::fmt;
+use crate::api_resource::capability::{ModelInfo, model_names};
 
-//noinspection SpellCheckingInspection
-#[derive(Debug, PartialEq, Eq, Clone)]
-pub enum ChatModelName {
-    AutoGlmPhone,
-    CodeGeeX,
-    CharGlm4,
-    Glm4Plus,
-    Glm4Air250414,
-    Glm4AirX,
-    Glm4Long,
-    Glm4FlashX,
-    Glm4Flash,
-    Glm4Flash250414,
-    Glm4VPlus0111,
-    Glm4VPlus,
-    Glm4V,
-    Glm4VFlash,
-    Glm4p1VThinkingFlash,
-    Glm4p1VThinkingFlashX,
-    GlmZeroPreview,
-    GlmZ1Air,
-    GlmZ1AirX,
-    GlmZ1Flash,
-    Glm4Voice,
-    Glm4AllTools,
-    Glm4p5,
-    Glm4p5Air,
-    Glm4p5X,
-    Glm4p5AirX,
-    Glm4p5Flash,
-    Glm4p5V,
-    Glm4p6,
-    Glm4p6V,
-    Glm4p6VFlash,
-    Glm4p7,
-}
-
-impl fmt::Display for ChatModelName {
+model_names! {
     //noinspection SpellCheckingInspection
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            Self::AutoGlmPhone => write!(f, "autoglm-phone"),
-            Self::Glm4Plus => write!(f, "glm-4-plus"),
-            Self::Glm4Air250414 => write!(f, "glm-4-air-250414"),
-            Self::Glm4AirX => write!(f, "glm-4-airx"),
-            Self::Glm4Long => write!(f, "glm-4-long"),
-            Self::Glm4FlashX => write!(f, "glm-4-flashx"),
-            Self::Glm4Flash => write!(f, "glm-4-flash"),
-            Self::Glm4Flash250414 => write!(f, "glm-4-flash-250414"),
-            Self::Glm4VPlus0111 => write!(f, "glm-4v-plus-0111"),
-            Self::Glm4VPlus => write!(f, "glm-4v-plus"),
-            Self::Glm4V => write!(f, "glm-4v"),
-            Self::Glm4VFlash => write!(f, "glm-4v-flash"),
-            Self::Glm4p1VThinkingFlash => write!(f, "GLM-4.1V-Thinking-Flash"),
-            Self::Glm4p1VThinkingFlashX => write!(f, "GLM-4.1V-Thinking-FlashX"),
-            Self::GlmZeroPreview => write!(f, "glm-zero-preview"),
-            Self::GlmZ1Air => write!(f, "glm-z1-air"),
-            Self::GlmZ1AirX => write!(f, "glm-z1-airx"),
-            Self::GlmZ1Flash => write!(f, "glm-z1-flash"),
-            Self::Glm4Voice => write!(f, "glm-4-voice"),
-            Self::Glm4AllTools => write!(f, "glm-4-alltools"),
-            Self::CodeGeeX => write!(f, "codegeex-4"),
-            Self::CharGlm4 => write!(f, "charglm-4"),
-            Self::Glm4p5 => write!(f, "glm-4.5"),
-            Self::Glm4p5Air => write!(f, "glm-4.5-air"),
-            Self::Glm4p5X => write!(f, "glm-4.5-x"),
-            Self::Glm4p5AirX => write!(f, "glm-4.5-airx"),
-            Self::Glm4p5Flash => write!(f, "glm-4.5-flash"),
-            Self::Glm4p5V => write!(f, "glm-4.5v"),
-            Self::Glm4p6 => write!(f, "glm-4.6"),
-            Self::Glm4p6V => write!(f, "glm-4.6v"),
-            Self::Glm4p6VFlash => write!(f, "glm-4.6v-flash"),
-            Self::Glm4p7 => write!(f, "glm-4.7"),
-        }
+    pub enum ChatModelName {
+        AutoGlmPhone => "autoglm-phone",
+        CodeGeeX => "codegeex-4",
+        CharGlm4 => "charglm-4",
+        Glm4Plus => "glm-4-plus",
+        Glm4Air250414 => "glm-4-air-250414",
+        Glm4AirX => "glm-4-airx" | "glm-4-air-x",
+        Glm4Long => "glm-4-long",
+        Glm4FlashX => "glm-4-flashx" | "glm-4-flash-x",
+        Glm4Flash => "glm-4-flash",
+        Glm4Flash250414 => "glm-4-flash-250414",
+        Glm4VPlus0111 => "glm-4v-plus-0111",
+        Glm4VPlus => "glm-4v-plus",
+        Glm4V => "glm-4v",
+        Glm4VFlash => "glm-4v-flash",
+        Glm4p1VThinkingFlash => "GLM-4.1V-Thinking-Flash",
+        Glm4p1VThinkingFlashX => "GLM-4.1V-Thinking-FlashX",
+        GlmZeroPreview => "glm-zero-preview",
+        GlmZ1Air => "glm-z1-air",
+        GlmZ1AirX => "glm-z1-airx",
+        GlmZ1Flash => "glm-z1-flash",
+        Glm4Voice => "glm-4-voice",
+        Glm4AllTools => "glm-4-alltools",
+        Glm4p5 => "glm-4.5",
+        Glm4p5Air => "glm-4.5-air",
+        Glm4p5X => "glm-4.5-x",
+        Glm4p5AirX => "glm-4.5-airx",
+        Glm4p5Flash => "glm-4.5-flash",
+        Glm4p5V => "glm-4.5v",
+        Glm4p6 => "glm-4.6",
+        Glm4p6V => "glm-4.6v",
+        Glm4p6VFlash => "glm-4.6v-flash",
+        Glm4p7 => "glm-4.7",
     }
 }
 
-impl From<ChatModelName> for &'static str {
+impl ChatModelName {
+    /// The capabilities of the model, `None` for [`Self::Custom`].
     //noinspection SpellCheckingInspection
-    fn from(model: ChatModelName) -> Self {
-        match model {
-            ChatModelName::AutoGlmPhone => "autoglm-phone",
-            ChatModelName::Glm4Plus => "glm-4-plus",
-            ChatModelName::Glm4Air250414 => "glm-4-air-250414",
-            ChatModelName::Glm4AirX => "glm-4-air-x",
-            ChatModelName::Glm4Long => "glm-4-long",
-            ChatModelName::Glm4FlashX => "glm-4-flash-x",
-            ChatModelName::Glm4Flash => "glm-4-flash",
-            ChatModelName::Glm4Flash250414 => "glm-4-flash-250414",
-            ChatModelName::Glm4VPlus0111 => "glm-4v-plus-0111",
-            ChatModelName::Glm4VPlus => "glm-4v-plus",
-            ChatModelName::Glm4V => "glm-4v",
-            ChatModelName::Glm4VFlash => "glm-4v-flash",
-            ChatModelName::Glm4p1VThinkingFlash => "GLM-4.1V-Thinking-Flash",
-            ChatModelName::Glm4p1VThinkingFlashX => "GLM-4.1V-Thinking-FlashX",
-            ChatModelName::GlmZeroPreview => "glm-zero-preview",
-            ChatModelName::GlmZ1Air => "glm-z1-air",
-            ChatModelName::GlmZ1AirX => "glm-z1-airx",
-            ChatModelName::GlmZ1Flash => "glm-z1-flash",
-            ChatModelName::Glm4Voice => "glm-4-voice",
-            ChatModelName::Glm4AllTools => "glm-4-alltools",
-            ChatModelName::CodeGeeX => "codegeex-4",
-            ChatModelName::CharGlm4 => "charglm-4",
-            ChatModelName::Glm4p5 => "glm-4.5",
-            ChatModelName::Glm4p5Air => "glm-4.5-air",
-            ChatModelName::Glm4p5X => "glm-4.5-x",
-            ChatModelName::Glm4p5AirX => "glm-4.5-airx",
-            ChatModelName::Glm4p5Flash => "glm-4.5-flash",
-            ChatModelName::Glm4p5V => "glm-4.5v",
-            ChatModelName::Glm4p6 => "glm-4.6",
-            ChatModelName::Glm4p6V => "glm-4.6v",
-            ChatModelName::Glm4p6VFlash => "glm-4.6v-flash",
-            ChatModelName::Glm4p7 => "glm-4.7",
-        }
+    pub fn info(&self) -> Option<ModelInfo> {
+        const K: u32 = 1024;
+        let name = self.static_name()?;
+        Some(match self {
+            Self::AutoGlmPhone => ModelInfo::new(name, 20 * K, 4 * K).vision(),
+            Self::CodeGeeX => ModelInfo::new(name, 128 * K, 32 * K),
+            Self::CharGlm4 => ModelInfo::new(name, 8 * K, 4 * K),
+            Self::Glm4Plus | Self::Glm4FlashX | Self::Glm4Flash | Self::Glm4AllTools => {
+                ModelInfo::new(name, 128 * K, 4 * K).tools().web_search()
+            }
+            Self::Glm4Air250414 | Self::Glm4Flash250414 => {
+                ModelInfo::new(name, 128 * K, 16 * K).tools().web_search()
+            }
+            Self::Glm4AirX => ModelInfo::new(name, 8 * K, 4 * K).tools().web_search(),
+            Self::Glm4Long => ModelInfo::new(name, 1024 * K, 4 * K).tools(),
+            Self::Glm4VPlus0111 => ModelInfo::new(name, 16 * K, 8 * K).vision().video(),
+            Self::Glm4VPlus => ModelInfo::new(name, 8 * K, K).vision().video(),
+            Self::Glm4V => ModelInfo::new(name, 2 * K, K).vision(),
+            Self::Glm4VFlash => ModelInfo::new(name, 8 * K, K).vision(),
+            Self::Glm4p1VThinkingFlash | Self::Glm4p1VThinkingFlashX => {
+                ModelInfo::new(name, 64 * K, 16 * K).vision().video()
+            }
+            Self::GlmZeroPreview => ModelInfo::new(name, 16 * K, 12 * K),
+            Self::GlmZ1Air | Self::GlmZ1Flash => ModelInfo::new(name, 128 * K, 32 * K),
+            Self::GlmZ1AirX => ModelInfo::new(name, 32 * K, 30 * K),
+            Self::Glm4Voice => ModelInfo::new(name, 8 * K, 4 * K).audio(),
+            Self::Glm4p5
+            | Self::Glm4p5Air
+            | Self::Glm4p5X
+            | Self::Glm4p5AirX
+            | Self::Glm4p5Flash => ModelInfo::new(name, 128 * K, 96 * K)
+                .tools()
+                .thinking()
+                .web_search(),
+            Self::Glm4p5V => ModelInfo::new(name, 64 * K, 16 * K)
+                .vision()
+                .video()
+                .thinking(),
+            Self::Glm4p6 | Self::Glm4p7 => ModelInfo::new(name, 200 * K, 128 * K)
+                .tools()
+                .thinking()
+                .web_search(),
+            Self::Glm4p6V | Self::Glm4p6VFlash => ModelInfo::new(name, 128 * K, 32 * K)
+                .vision()
+                .video()
+                .tools()
+                .thinking(),
+            Self::Custom(_) => return None,
+        })
     }
 }
@@ -1,13 +1,12 @@
-///! response of chat api
+//! response of chat api
 use super::data::*;
-use crate::error::ZhipuApiError;
-use async_stream::try_stream;
-use bytes::{Buf, BufMut, BytesMut};
+use crate::{endpoint::decode_response, error::ZhipuApiError};
+use async_stream::stream;
+use bytes::{Buf, BytesMut};
 use futures::StreamExt;
-use log::info;
 use reqwest::Response;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use std::fmt;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ChatApiResponse {
@@ -20,26 +19,111 @@ pub struct ChatApiResponse {
     web_search: Option<Vec<WebSearchResponse>>,
 }
 impl ChatApiResponse {
+    pub(super) fn new(
+        id: Option<String>,
+        created: Option<u64>,
+        model: Option<String>,
+        choices: Vec<Choice>,
+        usage: Option<Usage>,
+        web_search: Option<Vec<WebSearchResponse>>,
+    ) -> Self {
+        Self {
+            id,
+            created,
+            model,
+            choices: Some(choices),
+            request_id: None,
+            usage,
+            web_search,
+        }
+    }
+    pub fn get_id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+    pub fn get_model(&self) -> Option<&str> {
+        self.model.as_deref()
+    }
     pub fn get_choices(&self) -> Option<&Vec<Choice>> {
         self.choices.as_ref()
     }
     pub fn get_web_search(&self) -> &Option<Vec<WebSearchResponse>> {
         &self.web_search
     }
+    pub fn get_usage(&self) -> Option<&Usage> {
+        self.usage.as_ref()
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 struct Delta {
+    #[serde(default)]
     role: String,
     content: Option<String>,
     reasoning_content: Option<String>,
-    tool_calls: Option<Vec<ToolCall>>,
+    tool_calls: Option<Vec<ToolCallChunk>>,
+}
+
+/// A fragment of a tool call in a streamed chunk. The `arguments` of one call may be split across
+/// several chunks sharing the same `index`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ToolCallChunk {
+    #[serde(default)]
+    index: u32,
+    id: Option<String>,
+    #[serde(rename = "type")]
+    call_type: Option<String>,
+    function: Option<FunctionChunk>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct FunctionChunk {
+    name: Option<String>,
+    #[serde(default)]
+    arguments: Option<String>,
+}
+
+impl ToolCallChunk {
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+    pub fn call_type(&self) -> Option<&str> {
+        self.call_type.as_deref()
+    }
+    pub fn name(&self) -> Option<&str> {
+        self.function.as_ref().and_then(|f| f.name.as_deref())
+    }
+    pub fn arguments(&self) -> &str {
+        self.function
+            .as_ref()
+            .and_then(|f| f.arguments.as_deref())
+            .unwrap_or_default()
+    }
+}
+
+impl fmt::Display for ToolCallChunk {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(id) = &self.id {
+            writeln!(f, "ToolCall ID: {}", id)?;
+        }
+        writeln!(f, "Index: {}", self.index)?;
+        if let Some(call_type) = &self.call_type {
+            writeln!(f, "Type: {}", call_type)?;
+        }
+        if let Some(name) = self.name() {
+            writeln!(f, "Function Name: {}", name)?;
+        }
+        write!(f, "Arguments: {}", self.arguments())
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct ChoiceStream {
     index: u32,
     delta: Delta,
+    finish_reason: Option<String>,
 }
 
 impl ChoiceStream {
@@ -60,6 +144,14 @@ impl ChoiceStream {
         }
         string_data
     }
+
+    pub fn get_tool_call_chunks(&self) -> &[ToolCallChunk] {
+        self.delta.tool_calls.as_deref().unwrap_or(&[])
+    }
+
+    pub fn get_finish_reason(&self) -> Option<&str> {
+        self.finish_reason.as_deref()
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -73,12 +165,114 @@ pub struct ChatApiResponseStream {
 }
 
 impl ChatApiResponseStream {
+    pub fn get_id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+    pub fn get_created(&self) -> Option<u64> {
+        self.created
+    }
+    pub fn get_model(&self) -> Option<&str> {
+        self.model.as_deref()
+    }
     pub fn get_choices(&self) -> Option<&Vec<ChoiceStream>> {
         self.choices.as_ref()
     }
     pub fn get_web_search(&self) -> &Option<Vec<WebSearchResponse>> {
         &self.web_search
     }
+    pub fn get_usage(&self) -> Option<&Usage> {
+        self.usage.as_ref()
+    }
+
+    /// Split the chunk into events: web search results first, then the deltas and the finish
+    /// reason of every choice, and the usage last.
+    pub fn events(&self) -> Vec<ChatStreamEvent> {
+        let mut events = Vec::new();
+        if let Some(web_search) = &self.web_search {
+            events.push(ChatStreamEvent::WebSearch(web_search.clone()));
+        }
+        for choice in self.choices.iter().flatten() {
+            if let Some(reasoning) = choice
+                .delta
+                .reasoning_content
+                .as_ref()
+                .filter(|s| !s.is_empty())
+            {
+                events.push(ChatStreamEvent::ReasoningDelta(reasoning.clone()));
+            }
+            if let Some(content) = choice.delta.content.as_ref().filter(|s| !s.is_empty()) {
+                events.push(ChatStreamEvent::ContentDelta(content.clone()));
+            }
+            for call in choice.get_tool_call_chunks() {
+                events.push(ChatStreamEvent::ToolCallDelta {
+                    index: call.index,
+                    id: call.id.clone(),
+                    name: call.name().map(str::to_string),
+                    arguments_fragment: call.arguments().to_string(),
+                });
+            }
+            if let Some(reason) = &choice.finish_reason {
+                events.push(ChatStreamEvent::Finish {
+                    reason: reason.clone(),
+                });
+            }
+        }
+        if let Some(usage) = &self.usage {
+            events.push(ChatStreamEvent::Usage(usage.clone()));
+        }
+        events
+    }
+}
+
+/// One event of a streamed chat completion.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChatStreamEvent {
+    /// a piece of the chain of thought (`reasoning_content`)
+    ReasoningDelta(String),
+    /// a piece of the answer (`content`)
+    ContentDelta(String),
+    /// a piece of a tool call. The fragments of one call share its `index`; `id` and `name`
+    /// usually only come with the first one.
+    ToolCallDelta {
+        index: u32,
+        id: Option<String>,
+        name: Option<String>,
+        arguments_fragment: String,
+    },
+    /// the web search results used for the answer
+    WebSearch(Vec<WebSearchResponse>),
+    /// the token usage, sent with the last chunk
+    Usage(Usage),
+    /// why the model stopped, e.g. `stop`, `length`, `tool_calls`, `sensitive`
+    Finish { reason: String },
+    /// the `[DONE]` marker ending the stream
+    Done,
+}
+
+impl ChatStreamEvent {
+    /// the text of a content delta
+    pub fn content(&self) -> Option<&str> {
+        match self {
+            Self::ContentDelta(content) => Some(content),
+            _ => None,
+        }
+    }
+
+    /// the text of a reasoning delta
+    pub fn reasoning(&self) -> Option<&str> {
+        match self {
+            Self::ReasoningDelta(reasoning) => Some(reasoning),
+            _ => None,
+        }
+    }
+
+    /// whether the event carries generated output (reasoning, content or a tool call)
+    pub fn is_delta(&self) -> bool {
+        matches!(
+            self,
+            Self::ReasoningDelta(_) | Self::ContentDelta(_) | Self::ToolCallDelta { .. }
+        )
+    }
 }
 
 /// Fetches the entire response body and returns it.
@@ -87,132 +281,311 @@ pub async fn response_all(response: Response) -> Result<String, ZhipuApiError> {
         let response_text = response.text().await?;
         Ok(response_text)
     } else {
-        Err(ZhipuApiError::StatusCode(format!(
-            "Failed to fetch data: {}",
-            response.status()
-        )))
+        Err(ZhipuApiError::from_response(response).await)
     }
 }
 
 /// Parses and returns specific fields from the response body.
 pub async fn chat_response_context(response: Response) -> Result<ChatApiResponse, ZhipuApiError> {
-    if response.status().is_success() {
-        let response_text = response.text().await?;
-        info!("{}", response_text);
-        let api_response: ChatApiResponse = serde_json::from_str(&response_text)?;
-        Ok(api_response)
-    } else {
-        Err(ZhipuApiError::StatusCode(format!(
-            "Failed to fetch data: {}",
-            response.status()
-        )))
-    }
+    decode_response(response).await
 }
 
-/// Processes the response body as a stream, parsing chunks and yielding results.
+/// Processes the response body as a stream of [`ChatStreamEvent`]s.
+/// A chunk that cannot be parsed yields an `Err` and the stream goes on with the next one;
+/// a transport error ends the stream.
 pub fn response_context_stream(
     response: Response,
-) -> impl futures::Stream<Item = Result<String, ZhipuApiError>> {
-    try_stream! {
+) -> impl futures::Stream<Item = Result<ChatStreamEvent, ZhipuApiError>> {
+    sse_stream(response, chat_stream_events)
+}
+
+/// Processes the response body as a stream of raw chunks, which keep the id, creation time and
+/// model the events leave out. The stream ends at the `[DONE]` marker; errors are handled as
+/// by [`response_context_stream`].
+pub fn response_chunk_stream(
+    response: Response,
+) -> impl futures::Stream<Item = Result<ChatApiResponseStream, ZhipuApiError>> {
+    sse_stream(response, chat_stream_chunks)
+}
+
+/// Decodes the server-sent events of the response body with `decode`.
+fn sse_stream<T>(
+    response: Response,
+    decode: fn(&SseEvent) -> Vec<Result<T, ZhipuApiError>>,
+) -> impl futures::Stream<Item = Result<T, ZhipuApiError>> {
+    stream! {
         if !response.status().is_success() {
-            Err(ZhipuApiError::StatusCode(format!("Failed to fetch data: {}", response.status())))?;
+            yield Err(ZhipuApiError::from_response(response).await);
+            return;
         }
         let mut response_text = response.bytes_stream();
-        let mut buffer = BytesMut::new();
-        let mut string_buffer = String::new();
-        let mut thinking = false;
+        let mut decoder = SseDecoder::new();
         while let Some(chunk) = response_text.next().await {
-            let bytes = chunk?;
-            buffer.put(bytes);
-            // Handle UTF-8 decoding
-            decode_utf8(&mut buffer, &mut string_buffer)?;
-            // Process JSON objects
-            let processed_data = process_json_objects(&mut string_buffer, &mut thinking)?;
-            for data in processed_data {
-                yield data;
+            let bytes = match chunk {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    yield Err(e.into());
+                    return;
+                }
+            };
+            for sse in decoder.feed(&bytes) {
+                for item in decode(&sse) {
+                    yield item;
+                }
             }
         }
-        // Handle any remaining data
-        if !string_buffer.is_empty() {
-            yield format!("Remaining unprocessed data: {}", string_buffer);
+        // an event not followed by a blank line before the end of the body
+        if let Some(sse) = decoder.finish() {
+            for item in decode(&sse) {
+                yield item;
+            }
         }
     }
 }
 
-/// Decodes UTF-8 encoded bytes into a string buffer.
-fn decode_utf8(buffer: &mut BytesMut, string_buffer: &mut String) -> Result<(), ZhipuApiError> {
-    loop {
-        match std::str::from_utf8(buffer) {
-            Ok(s) => {
-                string_buffer.push_str(s);
-                buffer.clear();
-                break;
+/// Turns the `data` of one server-sent event into chat stream events.
+fn chat_stream_events(sse: &SseEvent) -> Vec<Result<ChatStreamEvent, ZhipuApiError>> {
+    let data = sse.data.trim();
+    if data.is_empty() {
+        Vec::new()
+    } else if data == "[DONE]" {
+        vec![Ok(ChatStreamEvent::Done)]
+    } else {
+        match serde_json::from_str::<ChatApiResponseStream>(data) {
+            Ok(chunk) => chunk.events().into_iter().map(Ok).collect(),
+            Err(e) => vec![Err(e.into())],
+        }
+    }
+}
+
+/// Turns the `data` of one server-sent event into a chunk, none for the `[DONE]` marker.
+fn chat_stream_chunks(sse: &SseEvent) -> Vec<Result<ChatApiResponseStream, ZhipuApiError>> {
+    let data = sse.data.trim();
+    if data.is_empty() || data == "[DONE]" {
+        Vec::new()
+    } else {
+        vec![serde_json::from_str(data).map_err(Into::into)]
+    }
+}
+
+/// One server-sent event.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SseEvent {
+    /// the `event:` field, `None` for the default `message` type
+    pub event: Option<String>,
+    /// the `data:` fields, joined with `\n`
+    pub data: String,
+    /// the last `id:` seen on the stream, it carries over to later events
+    pub id: Option<String>,
+    /// the `retry:` field, in milliseconds
+    pub retry: Option<u64>,
+}
+
+/// An incremental decoder of `text/event-stream` bodies, following the
+/// [WHATWG specification](https://html.spec.whatwg.org/multipage/server-sent-events.html#event-stream-interpretation).
+///
+/// Bytes can be fed in chunks of any size: a line, a UTF-8 sequence or a CRLF pair split across
+/// two chunks is put back together. Lines may end with LF, CRLF or CR, `:` lines are comments,
+/// and the space after the colon is optional.
+/// ```ignore
+/// let mut decoder = SseDecoder::new();
+/// while let Some(chunk) = body.next().await {
+///     for event in decoder.feed(&chunk?) {
+///         println!("{}", event.data);
+///     }
+/// }
+/// if let Some(event) = decoder.finish() {
+///     println!("{}", event.data);
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct SseDecoder {
+    buffer: BytesMut,
+    skip_lf: bool,
+    event: Option<String>,
+    data: String,
+    has_data: bool,
+    id: Option<String>,
+    retry: Option<u64>,
+}
+
+impl SseDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add bytes of the body and return the events they complete.
+    pub fn feed(&mut self, mut chunk: &[u8]) -> Vec<SseEvent> {
+        // the LF of a CRLF pair whose CR ended the previous chunk
+        if self.skip_lf && !chunk.is_empty() {
+            if chunk[0] == b'\n' {
+                chunk = &chunk[1..];
             }
-            Err(e) => {
-                let valid_up_to = e.valid_up_to();
-                if valid_up_to == 0 {
-                    break;
+            self.skip_lf = false;
+        }
+        self.buffer.extend_from_slice(chunk);
+        let mut events = Vec::new();
+        // CR and LF never occur inside a multi-byte UTF-8 sequence, so splitting bytes is safe
+        while let Some(end) = self.buffer.iter().position(|b| *b == b'\n' || *b == b'\r') {
+            let line = self.buffer.split_to(end);
+            let terminator = self.buffer[0];
+            self.buffer.advance(1);
+            if terminator == b'\r' {
+                match self.buffer.first() {
+                    Some(b'\n') => self.buffer.advance(1),
+                    Some(_) => {}
+                    None => self.skip_lf = true,
                 }
-                let valid_str = unsafe { std::str::from_utf8_unchecked(&buffer[..valid_up_to]) };
-                string_buffer.push_str(valid_str);
-                buffer.advance(valid_up_to);
+            }
+            if let Some(event) = self.process_line(&String::from_utf8_lossy(&line)) {
+                events.push(event);
             }
         }
+        events
     }
-    Ok(())
-}
-
-/// Processes JSON objects from a string buffer and returns a vector of processed data.
-fn process_json_objects(
-    string_buffer: &mut String,
-    thinking: &mut bool,
-) -> Result<Vec<String>, ZhipuApiError> {
-    let mut processed_data = Vec::new();
-    while let Some(end) = string_buffer.find('\n') {
-        let json_str = string_buffer[..end].trim();
-        if !json_str.is_empty() && json_str != "data: [DONE]" {
-            if let Some(json_str) = json_str.strip_prefix("data: ") {
-                match serde_json::from_str::<ChatApiResponseStream>(json_str) {
-                    Ok(api_response) => {
-                        if let Some(choices) = api_response.get_choices() {
-                            for message in choices {
-                                if !message.get_content().is_empty() {
-                                    if *thinking {
-                                        processed_data.push("\n</think>\n".to_owned());
-                                        *thinking = false;
-                                    }
-                                    processed_data.push(message.get_content().to_string());
-                                } else if !message.get_reasoning_content().is_empty() {
-                                    if !*thinking {
-                                        processed_data.push("<think>\n".to_owned());
-                                        *thinking = true;
-                                    }
-                                    processed_data
-                                        .push(message.get_reasoning_content().to_string());
-                                } else if !message.get_tool_calls().to_string().is_empty() {
-                                    if *thinking {
-                                        processed_data.push("\n</think>\n".to_owned());
-                                        *thinking = false;
-                                    }
-                                    processed_data.push(message.get_tool_calls().to_string());
-                                }
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        match serde_json::from_str::<Value>(json_str) {
-                           Ok(_) => processed_data.push(format!(
-                               "JSON format is correct, but does not match ChatApiResponseStream structure: {}",
-                               json_str
-                           )),
-                           Err(_) => processed_data.push(format!("Invalid JSON data: {}", json_str)),
-                       }
-                        processed_data.push(format!("Failed to parse API response: {}", e));
-                    }
+
+    /// Signal the end of the body. An unterminated last line and an event missing its blank line
+    /// are still returned instead of being dropped.
+    pub fn finish(&mut self) -> Option<SseEvent> {
+        if !self.buffer.is_empty() {
+            let line = self.buffer.split();
+            if let Some(event) = self.process_line(&String::from_utf8_lossy(&line)) {
+                return Some(event);
+            }
+        }
+        self.dispatch()
+    }
+
+    fn process_line(&mut self, line: &str) -> Option<SseEvent> {
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        if line.starts_with(':') {
+            return None;
+        }
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            "event" => self.event = Some(value.to_string()),
+            "data" => {
+                if self.has_data {
+                    self.data.push('\n');
+                }
+                self.data.push_str(value);
+                self.has_data = true;
+            }
+            "id" if !value.contains('\0') => self.id = Some(value.to_string()),
+            "retry" => {
+                if let Ok(retry) = value.parse() {
+                    self.retry = Some(retry);
                 }
             }
+            _ => {}
+        }
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<SseEvent> {
+        let event = self.event.take();
+        if !self.has_data {
+            return None;
         }
-        string_buffer.drain(..=end);
+        self.has_data = false;
+        Some(SseEvent {
+            event,
+            data: std::mem::take(&mut self.data),
+            id: self.id.clone(),
+            retry: self.retry.take(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stream_lines_become_events() {
+        let buffer = concat!(
+            "data: {\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\",\"reasoning_content\":\"hm\"}}]}\n\n",
+            "data: {\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\",\"tool_calls\":[{\"index\":0,\"id\":\"call_1\",\"type\":\"function\",\"function\":{\"name\":\"get_weather\",\"arguments\":\"{\\\"city\\\":\"}}]}}]}\n\n",
+            "data: not json\n\n",
+            "data: {\"choices\":[{\"index\":0,\"finish_reason\":\"tool_calls\",\"delta\":{\"role\":\"assistant\"}}],\"usage\":{\"prompt_tokens\":1,\"completion_tokens\":2,\"total_tokens\":3}}\n\n",
+            "data: [DONE]\n\n",
+        );
+        let events: Vec<_> = SseDecoder::new()
+            .feed(buffer.as_bytes())
+            .iter()
+            .flat_map(chat_stream_events)
+            .collect();
+        assert_eq!(events.len(), 6);
+        assert_eq!(
+            events[0].as_ref().unwrap(),
+            &ChatStreamEvent::ReasoningDelta("hm".to_string())
+        );
+        assert_eq!(
+            events[1].as_ref().unwrap(),
+            &ChatStreamEvent::ToolCallDelta {
+                index: 0,
+                id: Some("call_1".to_string()),
+                name: Some("get_weather".to_string()),
+                arguments_fragment: "{\"city\":".to_string(),
+            }
+        );
+        assert!(events[2].is_err());
+        assert_eq!(
+            events[3].as_ref().unwrap(),
+            &ChatStreamEvent::Finish {
+                reason: "tool_calls".to_string()
+            }
+        );
+        assert!(matches!(events[4], Ok(ChatStreamEvent::Usage(_))));
+        assert_eq!(events[5].as_ref().unwrap(), &ChatStreamEvent::Done);
+    }
+
+    fn feed_in_pieces(body: &[u8], size: usize) -> Vec<SseEvent> {
+        let mut decoder = SseDecoder::new();
+        let mut events: Vec<SseEvent> = body.chunks(size).flat_map(|c| decoder.feed(c)).collect();
+        events.extend(decoder.finish());
+        events
+    }
+
+    #[test]
+    fn test_chunk_boundaries_do_not_matter() {
+        let body = "data: {\"content\":\"你好，世界\"}\r\n\r\ndata: {\"content\":\"🙂\"}\r\n\r\n"
+            .as_bytes();
+        let whole = feed_in_pieces(body, body.len());
+        assert_eq!(whole.len(), 2);
+        assert_eq!(whole[0].data, "{\"content\":\"你好，世界\"}");
+        assert_eq!(whole[1].data, "{\"content\":\"🙂\"}");
+        // every split position, including inside UTF-8 sequences and CRLF pairs
+        for size in 1..body.len() {
+            assert_eq!(feed_in_pieces(body, size), whole, "chunk size {}", size);
+        }
+    }
+
+    #[test]
+    fn test_fields_comments_and_line_endings() {
+        let body = b": keepalive\rid: 7\revent: delta\rdata:first\ndata: second\r\nretry: 300\n\nevent: ping\n\ndata: tail";
+        let events = feed_in_pieces(body, 3);
+        assert_eq!(
+            events,
+            vec![
+                SseEvent {
+                    event: Some("delta".to_string()),
+                    data: "first\nsecond".to_string(),
+                    id: Some("7".to_string()),
+                    retry: Some(300),
+                },
+                SseEvent {
+                    event: None,
+                    data: "tail".to_string(),
+                    id: Some("7".to_string()),
+                    retry: None,
+                },
+            ]
+        );
     }
-    Ok(processed_data)
 }
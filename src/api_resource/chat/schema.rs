@@ -0,0 +1,786 @@
+//! # Function parameters
+//!
+//! [`Parameters`] and [`Property`] model the JSON Schema the platform accepts for the parameters
+//! of a function: types, descriptions, `enum`, `items`, nested objects with `required`, defaults
+//! and bounds. Keywords without a typed field are kept and sent as they are. With the `schemars`
+//! feature, `Parameters::from_type` derives the schema from a Rust type and its doc comments.
+//! The arguments chosen by the model are checked against it by [`Parameters::check`].
+
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Value};
+use std::{collections::HashMap, error::Error, fmt};
+
+/// The JSON Schema of one value.
+/// ```ignore
+/// let city = Property::string("the city to search").min_length(2);
+/// let days = Property::integer("days of forecast").minimum(1.0).maximum(7.0).default_value(3);
+/// let unit = Property::string("temperature unit").enum_values(["celsius", "fahrenheit"]);
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Property {
+    #[serde(
+        rename = "type",
+        default,
+        skip_serializing_if = "Vec::is_empty",
+        serialize_with = "serialize_types",
+        deserialize_with = "deserialize_types"
+    )]
+    types: Vec<String>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    description: String,
+    #[serde(rename = "enum", skip_serializing_if = "Option::is_none")]
+    enum_values: Option<Vec<Value>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    default: Option<Value>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "deserialize_items"
+    )]
+    items: Option<Box<Property>>,
+    #[serde(
+        default,
+        skip_serializing_if = "HashMap::is_empty",
+        deserialize_with = "deserialize_properties"
+    )]
+    properties: HashMap<String, Property>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    required: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    minimum: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    maximum: Option<f64>,
+    #[serde(rename = "minLength", skip_serializing_if = "Option::is_none")]
+    min_length: Option<u64>,
+    #[serde(rename = "maxLength", skip_serializing_if = "Option::is_none")]
+    max_length: Option<u64>,
+    #[serde(rename = "minItems", skip_serializing_if = "Option::is_none")]
+    min_items: Option<u64>,
+    #[serde(rename = "maxItems", skip_serializing_if = "Option::is_none")]
+    max_items: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pattern: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<String>,
+    /// any other keyword
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+impl Property {
+    /// A value of the JSON type `prop_type`: string, number, integer, boolean, array, object or
+    /// null.
+    pub fn new(prop_type: &str, description: &str) -> Property {
+        Property {
+            types: vec![prop_type.to_string()],
+            description: description.to_string(),
+            ..Default::default()
+        }
+    }
+    pub fn string(description: &str) -> Property {
+        Self::new("string", description)
+    }
+    pub fn integer(description: &str) -> Property {
+        Self::new("integer", description)
+    }
+    pub fn number(description: &str) -> Property {
+        Self::new("number", description)
+    }
+    pub fn boolean(description: &str) -> Property {
+        Self::new("boolean", description)
+    }
+    /// any JSON value
+    pub fn any(description: &str) -> Property {
+        Property {
+            description: description.to_string(),
+            ..Default::default()
+        }
+    }
+    /// an array whose elements all match `items`
+    pub fn array(description: &str, items: Property) -> Property {
+        Property {
+            items: Some(Box::new(items)),
+            ..Self::new("array", description)
+        }
+    }
+    /// an object, its fields are added with [`Self::property`] and [`Self::optional_property`]
+    pub fn object(description: &str) -> Property {
+        Self::new("object", description)
+    }
+    /// add a required field to an object
+    pub fn property(mut self, name: &str, property: Property) -> Self {
+        self.required.retain(|field| field != name);
+        self.required.push(name.to_string());
+        self.properties.insert(name.to_string(), property);
+        self
+    }
+    /// add a field the model may leave out to an object
+    pub fn optional_property(mut self, name: &str, property: Property) -> Self {
+        self.required.retain(|field| field != name);
+        self.properties.insert(name.to_string(), property);
+        self
+    }
+    /// also accept `null`
+    pub fn nullable(mut self) -> Self {
+        if !self.types.is_empty() && !self.types.iter().any(|t| t == "null") {
+            self.types.push("null".to_string());
+        }
+        self
+    }
+    /// restrict the value to one of `values`
+    pub fn enum_values<V: Into<Value>>(mut self, values: impl IntoIterator<Item = V>) -> Self {
+        self.enum_values = Some(values.into_iter().map(Into::into).collect());
+        self
+    }
+    /// the value assumed when the field is left out
+    pub fn default_value(mut self, value: impl Into<Value>) -> Self {
+        self.default = Some(value.into());
+        self
+    }
+    pub fn minimum(mut self, minimum: f64) -> Self {
+        self.minimum = Some(minimum);
+        self
+    }
+    pub fn maximum(mut self, maximum: f64) -> Self {
+        self.maximum = Some(maximum);
+        self
+    }
+    /// the fewest characters of a string
+    pub fn min_length(mut self, min_length: u64) -> Self {
+        self.min_length = Some(min_length);
+        self
+    }
+    /// the most characters of a string
+    pub fn max_length(mut self, max_length: u64) -> Self {
+        self.max_length = Some(max_length);
+        self
+    }
+    /// the fewest elements of an array
+    pub fn min_items(mut self, min_items: u64) -> Self {
+        self.min_items = Some(min_items);
+        self
+    }
+    /// the most elements of an array
+    pub fn max_items(mut self, max_items: u64) -> Self {
+        self.max_items = Some(max_items);
+        self
+    }
+    /// a regular expression a string must match
+    pub fn pattern(mut self, pattern: &str) -> Self {
+        self.pattern = Some(pattern.to_string());
+        self
+    }
+    /// a hint such as `date`, `date-time` or `email`, not checked
+    pub fn format(mut self, format: &str) -> Self {
+        self.format = Some(format.to_string());
+        self
+    }
+    /// the accepted JSON types, empty when any is
+    pub fn types(&self) -> &[String] {
+        &self.types
+    }
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+    pub fn get_enum_values(&self) -> Option<&[Value]> {
+        self.enum_values.as_deref()
+    }
+    pub fn get_default(&self) -> Option<&Value> {
+        self.default.as_ref()
+    }
+    /// the schema of the elements of an array
+    pub fn items(&self) -> Option<&Property> {
+        self.items.as_deref()
+    }
+    /// the fields of an object
+    pub fn properties(&self) -> &HashMap<String, Property> {
+        &self.properties
+    }
+    /// the fields of an object the model must fill
+    pub fn required(&self) -> &[String] {
+        &self.required
+    }
+}
+
+/// The parameters of a function: the schema of the object of arguments.
+/// ```ignore
+/// let parameters = Parameters::default()
+///     .property("city", Property::string("the city to search"))
+///     .optional_property(
+///         "days",
+///         Property::integer("days of forecast").minimum(1.0).maximum(7.0),
+///     );
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Parameters {
+    #[serde(flatten)]
+    schema: Property,
+}
+
+impl Default for Parameters {
+    /// an object without fields
+    fn default() -> Self {
+        Self {
+            schema: Property::object(""),
+        }
+    }
+}
+
+impl Parameters {
+    /// An object with `properties`, all of them required.
+    pub fn new(properties: HashMap<String, Property>) -> Self {
+        let mut required: Vec<String> = properties.keys().map(|k| k.to_string()).collect();
+        required.sort();
+        Self {
+            schema: Property {
+                properties,
+                required,
+                ..Property::object("")
+            },
+        }
+    }
+    /// Derive the parameters from `T`: its fields, their types and doc comments, `Option` fields
+    /// being optional. Subschemas are inlined, as the platform does not resolve `$ref`. Fails when
+    /// the generated schema does not fit [`Property`].
+    /// ```ignore
+    /// #[derive(Deserialize, JsonSchema)]
+    /// struct Weather {
+    ///     /// the city to search
+    ///     city: String,
+    ///     /// days of forecast, 3 by default
+    ///     days: Option<u32>,
+    /// }
+    /// let function = Function::new("get_weather", "the weather", Parameters::from_type::<Weather>()?);
+    /// ```
+    #[cfg(feature = "schemars")]
+    pub fn from_type<T: schemars::JsonSchema>() -> Result<Self, serde_json::Error> {
+        let generator = schemars::generate::SchemaSettings::default()
+            .with(|settings| {
+                settings.inline_subschemas = true;
+                settings.meta_schema = None;
+            })
+            .into_generator();
+        let mut schema = generator.into_root_schema_for::<T>().to_value();
+        if let Value::Object(schema) = &mut schema {
+            schema.remove("title");
+        }
+        serde_json::from_value(schema)
+    }
+    /// add a required field
+    pub fn property(mut self, name: &str, property: Property) -> Self {
+        self.schema = self.schema.property(name, property);
+        self
+    }
+    /// add a field the model may leave out
+    pub fn optional_property(mut self, name: &str, property: Property) -> Self {
+        self.schema = self.schema.optional_property(name, property);
+        self
+    }
+    /// the schema of the whole object
+    pub fn schema(&self) -> &Property {
+        &self.schema
+    }
+    pub fn properties(&self) -> &HashMap<String, Property> {
+        self.schema.properties()
+    }
+    /// the names of the properties the model must fill
+    pub fn required(&self) -> &[String] {
+        self.schema.required()
+    }
+}
+
+impl From<Property> for Parameters {
+    fn from(schema: Property) -> Self {
+        Self { schema }
+    }
+}
+
+/// A type a parameter of a `#[zhipu_tool]` function may have: its schema. Implement it for your
+/// own types, or give the schema of a parameter with `#[param(schema = ...)]`.
+pub trait ToolParameter {
+    fn property(description: &str) -> Property;
+}
+
+macro_rules! tool_parameter {
+    ($constructor:ident: $($ty:ty),*) => {
+        $(impl ToolParameter for $ty {
+            fn property(description: &str) -> Property {
+                Property::$constructor(description)
+            }
+        })*
+    };
+}
+
+tool_parameter!(string: String, Box<str>);
+tool_parameter!(boolean: bool);
+tool_parameter!(integer: i8, i16, i32, i64, i128, isize);
+tool_parameter!(number: f32, f64);
+tool_parameter!(any: Value);
+
+macro_rules! unsigned_tool_parameter {
+    ($($ty:ty),*) => {
+        $(impl ToolParameter for $ty {
+            fn property(description: &str) -> Property {
+                Property::integer(description).minimum(0.0)
+            }
+        })*
+    };
+}
+
+unsigned_tool_parameter!(u8, u16, u32, u64, u128, usize);
+
+impl ToolParameter for char {
+    fn property(description: &str) -> Property {
+        Property::string(description).min_length(1).max_length(1)
+    }
+}
+
+impl<T: ToolParameter> ToolParameter for Option<T> {
+    fn property(description: &str) -> Property {
+        T::property(description).nullable()
+    }
+}
+
+impl<T: ToolParameter> ToolParameter for Box<T> {
+    fn property(description: &str) -> Property {
+        T::property(description)
+    }
+}
+
+impl<T: ToolParameter> ToolParameter for Vec<T> {
+    fn property(description: &str) -> Property {
+        Property::array(description, T::property(""))
+    }
+}
+
+impl<T: ToolParameter> ToolParameter for HashMap<String, T> {
+    fn property(description: &str) -> Property {
+        let mut property = Property::object(description);
+        property.extra.insert(
+            "additionalProperties".to_string(),
+            serde_json::to_value(T::property("")).unwrap_or_default(),
+        );
+        property
+    }
+}
+
+/// `type` is a name or a list of names
+fn serialize_types<S: Serializer>(types: &[String], serializer: S) -> Result<S::Ok, S::Error> {
+    match types {
+        [single] => serializer.serialize_str(single),
+        types => types.serialize(serializer),
+    }
+}
+
+fn deserialize_types<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Types {
+        Single(String),
+        Many(Vec<String>),
+    }
+    Ok(match Types::deserialize(deserializer)? {
+        Types::Single(single) => vec![single],
+        Types::Many(types) => types,
+    })
+}
+
+/// A subschema, where `true` stands for any value and `false` for none.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SubSchema {
+    Boolean(bool),
+    Schema(Box<Property>),
+}
+
+impl From<SubSchema> for Property {
+    fn from(schema: SubSchema) -> Self {
+        match schema {
+            SubSchema::Boolean(true) => Property::default(),
+            SubSchema::Boolean(false) => Property {
+                extra: Map::from_iter([("not".to_string(), Value::Object(Map::new()))]),
+                ..Default::default()
+            },
+            SubSchema::Schema(schema) => *schema,
+        }
+    }
+}
+
+fn deserialize_items<'de, D>(deserializer: D) -> Result<Option<Box<Property>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(Option::<SubSchema>::deserialize(deserializer)?.map(|items| Box::new(items.into())))
+}
+
+fn deserialize_properties<'de, D>(deserializer: D) -> Result<HashMap<String, Property>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let properties = HashMap::<String, SubSchema>::deserialize(deserializer)?;
+    Ok(properties
+        .into_iter()
+        .map(|(name, schema)| (name, schema.into()))
+        .collect())
+}
+
+/// A way the arguments of a function call differ from its [`Parameters`]. Fields of nested
+/// objects are named by their path, such as `rooms[0].beds`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArgumentIssue {
+    /// the arguments are not a JSON object
+    NotAnObject,
+    /// a required field is absent
+    Missing(String),
+    /// a field holds another JSON type than declared
+    WrongType {
+        field: String,
+        expected: String,
+        found: &'static str,
+    },
+    /// a field breaks its `enum`, bounds or pattern
+    Invalid { field: String, reason: String },
+}
+
+impl fmt::Display for ArgumentIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotAnObject => write!(f, "the arguments are not a JSON object"),
+            Self::Missing(field) => write!(f, "missing field `{}`", field),
+            Self::Invalid { field, reason } => write!(f, "field `{}` {}", field, reason),
+            Self::WrongType {
+                field,
+                expected,
+                found,
+            } => write!(
+                f,
+                "field `{}` should be {}, found {}",
+                field, expected, found
+            ),
+        }
+    }
+}
+
+/// Every [`ArgumentIssue`] found in the arguments of a function call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArgumentsError {
+    pub issues: Vec<ArgumentIssue>,
+}
+
+impl fmt::Display for ArgumentsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let issues: Vec<String> = self.issues.iter().map(|i| i.to_string()).collect();
+        write!(f, "invalid arguments: {}", issues.join("; "))
+    }
+}
+
+impl Error for ArgumentsError {}
+
+impl Parameters {
+    /// Check `arguments` against the schema: required fields must be present and every declared
+    /// field must match its type, `enum`, bounds and pattern, nested objects and arrays
+    /// included. Undeclared fields are ignored.
+    pub fn check(&self, arguments: &Value) -> Result<(), ArgumentsError> {
+        if !arguments.is_object() {
+            return Err(ArgumentsError {
+                issues: vec![ArgumentIssue::NotAnObject],
+            });
+        }
+        let mut issues = Vec::new();
+        check_value(&self.schema, arguments, "", &mut issues);
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            issues.sort_by_key(|issue| issue.to_string());
+            Err(ArgumentsError { issues })
+        }
+    }
+}
+
+/// Push the issues of `value` at `path` into `issues`.
+fn check_value(schema: &Property, value: &Value, path: &str, issues: &mut Vec<ArgumentIssue>) {
+    let mut invalid = |reason: String| {
+        issues.push(ArgumentIssue::Invalid {
+            field: path.to_string(),
+            reason,
+        })
+    };
+    if !schema.types.is_empty() && !schema.types.iter().any(|t| matches_type(t, value)) {
+        issues.push(ArgumentIssue::WrongType {
+            field: path.to_string(),
+            expected: schema.types.join(" or "),
+            found: type_name(value),
+        });
+        return;
+    }
+    if let Some(values) = &schema.enum_values
+        && !values.contains(value)
+    {
+        let values: Vec<String> = values.iter().map(Value::to_string).collect();
+        invalid(format!("should be one of {}", values.join(", ")));
+    }
+    match value {
+        Value::Number(number) => {
+            let number = number.as_f64().unwrap_or_default();
+            if let Some(minimum) = schema.minimum
+                && number < minimum
+            {
+                invalid(format!("should be at least {}", minimum));
+            }
+            if let Some(maximum) = schema.maximum
+                && number > maximum
+            {
+                invalid(format!("should be at most {}", maximum));
+            }
+        }
+        Value::String(text) => {
+            let length = text.chars().count() as u64;
+            if let Some(min_length) = schema.min_length
+                && length < min_length
+            {
+                invalid(format!("should have at least {} characters", min_length));
+            }
+            if let Some(max_length) = schema.max_length
+                && length > max_length
+            {
+                invalid(format!("should have at most {} characters", max_length));
+            }
+            if let Some(pattern) = &schema.pattern
+                && let Ok(regex) = Regex::new(pattern)
+                && !regex.is_match(text)
+            {
+                invalid(format!("should match `{}`", pattern));
+            }
+        }
+        Value::Array(elements) => {
+            let count = elements.len() as u64;
+            if let Some(min_items) = schema.min_items
+                && count < min_items
+            {
+                invalid(format!("should have at least {} elements", min_items));
+            }
+            if let Some(max_items) = schema.max_items
+                && count > max_items
+            {
+                invalid(format!("should have at most {} elements", max_items));
+            }
+            if let Some(items) = &schema.items {
+                for (index, element) in elements.iter().enumerate() {
+                    check_value(items, element, &format!("{}[{}]", path, index), issues);
+                }
+            }
+        }
+        Value::Object(fields) => {
+            let field_path = |field: &str| match path {
+                "" => field.to_string(),
+                path => format!("{}.{}", path, field),
+            };
+            for field in &schema.required {
+                if !fields.contains_key(field) {
+                    issues.push(ArgumentIssue::Missing(field_path(field)));
+                }
+            }
+            for (field, value) in fields {
+                if let Some(property) = schema.properties.get(field) {
+                    check_value(property, value, &field_path(field), issues);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Whether `value` is of the JSON schema type `expected`; unknown types accept anything.
+fn matches_type(expected: &str, value: &Value) -> bool {
+    match expected {
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "boolean" => value.is_boolean(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(number) if number.is_f64() => "number",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_resource::chat::ToolCall;
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_typed_arguments() {
+        let call: ToolCall = serde_json::from_str(
+            r#"{"id":"call_1","type":"function","function":{"name":"book",
+                "arguments":"{\"city\":\"北京\",\"nights\":2,\"tags\":[\"quiet\"],\"pets\":false}"}}"#,
+        )
+        .unwrap();
+
+        #[derive(Deserialize)]
+        struct Booking {
+            city: String,
+            nights: u32,
+            tags: Vec<String>,
+        }
+        let booking: Booking = call.parse_args().unwrap();
+        assert_eq!(
+            (booking.city.as_str(), booking.nights, booking.tags.len()),
+            ("北京", 2, 1)
+        );
+        let echoed = serde_json::to_value(&call).unwrap();
+        assert!(echoed["function"]["arguments"].is_string());
+
+        let parameters = Parameters::new(HashMap::from([
+            ("city".to_string(), Property::new("string", "city")),
+            ("nights".to_string(), Property::new("string", "nights")),
+            ("guests".to_string(), Property::new("integer", "guests")),
+        ]));
+        let error = call.function().unwrap().validate(&parameters).unwrap_err();
+        assert_eq!(
+            error.issues,
+            vec![
+                ArgumentIssue::WrongType {
+                    field: "nights".to_string(),
+                    expected: "string".to_string(),
+                    found: "integer",
+                },
+                ArgumentIssue::Missing("guests".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_nested_schema() {
+        let parameters = Parameters::default()
+            .property("city", Property::string("city").min_length(2))
+            .optional_property(
+                "rooms",
+                Property::array(
+                    "rooms",
+                    Property::object("room")
+                        .property("beds", Property::integer("beds").minimum(1.0))
+                        .optional_property(
+                            "view",
+                            Property::string("view").enum_values(["sea", "city"]),
+                        ),
+                )
+                .max_items(3),
+            );
+        let schema = serde_json::to_value(&parameters).unwrap();
+        assert_eq!(schema["type"], "object");
+        assert_eq!(schema["required"], serde_json::json!(["city"]));
+        assert_eq!(
+            schema["properties"]["rooms"]["items"]["required"][0],
+            "beds"
+        );
+        assert_eq!(schema["properties"]["rooms"]["maxItems"], 3);
+        assert_eq!(
+            serde_json::from_value::<Parameters>(schema).unwrap(),
+            parameters
+        );
+
+        let error = parameters
+            .check(&serde_json::json!({
+                "city": "京",
+                "rooms": [{"beds": 2, "view": "sea"}, {"beds": 0, "view": "garden"}, {}]
+            }))
+            .unwrap_err();
+        let issues: Vec<String> = error.issues.iter().map(|i| i.to_string()).collect();
+        assert_eq!(
+            issues,
+            [
+                "field `city` should have at least 2 characters",
+                "field `rooms[1].beds` should be at least 1",
+                "field `rooms[1].view` should be one of \"sea\", \"city\"",
+                "missing field `rooms[2].beds`",
+            ]
+        );
+    }
+
+    #[cfg(feature = "schemars")]
+    #[test]
+    fn test_parameters_from_type() {
+        #[allow(dead_code)]
+        #[derive(schemars::JsonSchema)]
+        struct Weather {
+            /// the city to search
+            city: String,
+            /// days of forecast
+            days: Option<u32>,
+            unit: Unit,
+        }
+        #[allow(dead_code)]
+        #[derive(schemars::JsonSchema)]
+        enum Unit {
+            Celsius,
+            Fahrenheit,
+        }
+
+        let parameters = Parameters::from_type::<Weather>().unwrap();
+        assert_eq!(parameters.required(), ["city", "unit"]);
+        assert_eq!(
+            parameters.properties()["city"].description(),
+            "the city to search"
+        );
+        assert_eq!(parameters.properties()["days"].types(), ["integer", "null"]);
+        assert_eq!(
+            parameters.properties()["unit"]
+                .get_enum_values()
+                .unwrap()
+                .len(),
+            2
+        );
+        assert!(
+            parameters
+                .check(&serde_json::json!({"city": "北京", "days": null, "unit": "Celsius"}))
+                .is_ok()
+        );
+        assert!(!serde_json::to_string(&parameters).unwrap().contains("$ref"));
+    }
+
+    #[cfg(feature = "schemars")]
+    #[test]
+    fn test_parameters_from_nested_type() {
+        #[allow(dead_code)]
+        #[derive(schemars::JsonSchema)]
+        struct Booking {
+            guest: Guest,
+            rooms: Vec<Room>,
+        }
+        #[allow(dead_code)]
+        #[derive(schemars::JsonSchema)]
+        struct Guest {
+            name: String,
+            age: Option<u8>,
+        }
+        #[allow(dead_code)]
+        #[derive(schemars::JsonSchema)]
+        struct Room {
+            beds: u8,
+        }
+
+        let parameters = Parameters::from_type::<Booking>().unwrap();
+        assert_eq!(parameters.required(), ["guest", "rooms"]);
+        assert_eq!(parameters.properties()["guest"].required(), ["name"]);
+        let issues = parameters
+            .check(&serde_json::json!({"guest": {"name": "李"}, "rooms": [{"beds": "two"}]}))
+            .unwrap_err();
+        assert_eq!(issues.issues.len(), 1);
+    }
+}
@@ -0,0 +1,103 @@
+//! JSON replies: find the JSON in the text of the model and decode it
+use serde::de::DeserializeOwned;
+
+/// The JSON in a reply: the content of a ```` ```json ```` fence when there is one, otherwise
+/// the text from the first `{` or `[` to the last `}` or `]`, otherwise the trimmed text.
+pub fn extract_json(reply: &str) -> &str {
+    let mut text = reply.trim();
+    if let Some(start) = text.find("```") {
+        let fenced = &text[start + 3..];
+        // skip the language tag, if the first line is one rather than the start of the JSON
+        let fenced = match fenced.split_once('\n') {
+            Some((tag, rest)) if tag.trim().chars().all(char::is_alphanumeric) => rest,
+            _ => fenced,
+        };
+        text = fenced
+            .find("```")
+            .map_or(fenced, |end| &fenced[..end])
+            .trim();
+    }
+    let start = text.find(['{', '[']);
+    let end = text.rfind(['}', ']']);
+    match (start, end) {
+        (Some(start), Some(end)) if start < end => &text[start..=end],
+        _ => text,
+    }
+}
+
+/// Deserialize the JSON of a reply into `T`, see [`extract_json`].
+pub fn parse_json<T: DeserializeOwned>(reply: &str) -> Result<T, serde_json::Error> {
+    serde_json::from_str(extract_json(reply))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        api_resource::chat::{ChatApiRequestBuilder, Message},
+        testing::{Mock, MockResponse, MockServer, fixtures},
+    };
+    use serde::Deserialize;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Translation {
+        text: String,
+        language: String,
+    }
+
+    #[test]
+    fn test_extract_json() {
+        let reply = "好的:\n```json\n{\"text\": \"hello\"}\n```\n希望有帮助";
+        assert_eq!(extract_json(reply), "{\"text\": \"hello\"}");
+        assert_eq!(extract_json("The list is [1, 2] ok"), "[1, 2]");
+        assert_eq!(extract_json(" plain "), "plain");
+        let inline = "```{\n  \"text\": \"hello\"\n}\n```";
+        assert_eq!(extract_json(inline), "{\n  \"text\": \"hello\"\n}");
+    }
+
+    #[tokio::test]
+    async fn test_send_json_repairs_the_reply() {
+        let server = MockServer::start().await.unwrap();
+        server
+            .mock(Mock::post(
+                "/chat/completions",
+                MockResponse::json(&fixtures::chat_completion(
+                    "```json\n{\"text\": \"hello\"}\n```",
+                )),
+            ))
+            .mock(Mock::post(
+                "/chat/completions",
+                MockResponse::json(&fixtures::chat_completion(
+                    "{\"text\": \"hello\", \"language\": \"en\"}",
+                )),
+            ));
+        let mut builder = ChatApiRequestBuilder::new("glm-4-flash");
+        builder.add_message(Message::user("翻译: 你好"));
+
+        let translation: Translation = builder
+            .send_json_repairing(&server.client(), 2)
+            .await
+            .unwrap();
+        assert_eq!(translation.language, "en");
+        let sent = server.received()[1].json();
+        assert_eq!(sent["response_format"]["type"], "json_object");
+        assert_eq!(sent["messages"][1]["role"], "assistant");
+        assert!(
+            sent["messages"][2]["content"]
+                .as_str()
+                .unwrap()
+                .contains("missing field `language`")
+        );
+
+        server.mock(Mock::post(
+            "/chat/completions",
+            MockResponse::json(&fixtures::chat_completion("no JSON here")),
+        ));
+        assert!(
+            builder
+                .send_json::<Translation>(&server.client())
+                .await
+                .is_err()
+        );
+    }
+}
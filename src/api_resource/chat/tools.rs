@@ -0,0 +1,539 @@
+//! run the functions called by the model and send their results back
+use super::{conversation::*, data::*, response::*};
+use crate::{client::ZhipuClient, error::ZhipuApiError};
+use futures::future::{BoxFuture, join_all};
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    error::Error,
+    fmt,
+    future::Future,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// What a tool handler returns: the content sent back to the model, or an error whose message
+/// is sent back instead.
+pub type ToolResult = Result<String, Box<dyn Error + Send + Sync>>;
+
+/// What a tool function may return, see `#[zhipu_tool]`.
+pub trait IntoToolResult {
+    fn into_tool_result(self) -> ToolResult;
+}
+
+impl<T, E> IntoToolResult for Result<T, E>
+where
+    T: Serialize,
+    E: Into<Box<dyn Error + Send + Sync>>,
+{
+    /// a string is sent as it is, anything else as JSON
+    fn into_tool_result(self) -> ToolResult {
+        match serde_json::to_value(self.map_err(Into::into)?)? {
+            Value::String(content) => Ok(content),
+            content => Ok(content.to_string()),
+        }
+    }
+}
+
+impl IntoToolResult for String {
+    fn into_tool_result(self) -> ToolResult {
+        Ok(self)
+    }
+}
+
+type Handler = Arc<dyn Fn(Value) -> BoxFuture<'static, ToolResult> + Send + Sync>;
+type ApprovalHook = Arc<dyn Fn(ToolInvocation) -> BoxFuture<'static, bool> + Send + Sync>;
+
+/// [`Function`] definitions and the async Rust handlers that implement them.
+/// ```ignore
+/// let registry = ToolRegistry::new().register(
+///     Function::new("get_weather", "the weather of a city", parameters),
+///     |arguments: serde_json::Value| async move {
+///         let city = arguments["city"].as_str().unwrap_or_default().to_string();
+///         Ok(format!("{}: sunny, 25°C", city))
+///     },
+/// );
+/// ```
+#[derive(Clone, Default)]
+pub struct ToolRegistry {
+    functions: Vec<(Function, Handler)>,
+}
+
+impl ToolRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a function, replacing any registered under the same name. The handler receives the
+    /// arguments chosen by the model as a JSON object.
+    pub fn register<F, Fut>(mut self, function: Function, handler: F) -> Self
+    where
+        F: Fn(Value) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ToolResult> + Send + 'static,
+    {
+        self.functions.retain(|(f, _)| f.name() != function.name());
+        let handler: Handler = Arc::new(move |arguments| Box::pin(handler(arguments)));
+        self.functions.push((function, handler));
+        self
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.function(name).is_some()
+    }
+
+    /// the definition registered under `name`
+    pub fn function(&self, name: &str) -> Option<&Function> {
+        self.functions
+            .iter()
+            .find(|(f, _)| f.name() == name)
+            .map(|(function, _)| function)
+    }
+
+    /// the registered functions, as tools of a chat request
+    pub fn tools(&self) -> Vec<Tool> {
+        self.functions
+            .iter()
+            .map(|(function, _)| Tool::new().function(function.clone()))
+            .collect()
+    }
+
+    /// Run the handler of `name`.
+    pub async fn call(&self, name: &str, arguments: Value) -> ToolResult {
+        let handler = self
+            .functions
+            .iter()
+            .find(|(f, _)| f.name() == name)
+            .map(|(_, handler)| handler.clone())
+            .ok_or_else(|| format!("unknown tool `{}`", name))?;
+        handler(arguments).await
+    }
+}
+
+impl fmt::Debug for ToolRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.functions.iter().map(|(function, _)| function.name()))
+            .finish()
+    }
+}
+
+/// One function call requested by the model.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolInvocation {
+    /// the id of the tool call, echoed in the result message
+    pub id: Option<String>,
+    pub name: String,
+    pub arguments: Value,
+}
+
+/// How a call ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ToolOutcome {
+    /// the handler returned this content
+    Success(String),
+    /// the handler failed, the arguments are invalid or no handler is registered under the name
+    Failure(String),
+    /// the approval hook refused the call, the handler did not run
+    Denied,
+}
+
+impl ToolOutcome {
+    /// the content of the tool message sent back to the model
+    pub fn content(&self) -> String {
+        match self {
+            Self::Success(content) => content.clone(),
+            Self::Failure(error) => format!("error: {}", error),
+            Self::Denied => "error: the call was not approved".to_string(),
+        }
+    }
+}
+
+/// An entry of the transcript of a [`ToolRun`].
+#[derive(Debug, Clone)]
+pub struct ToolRecord {
+    /// the round trip with the model the call belongs to, starting at 1
+    pub iteration: usize,
+    pub invocation: ToolInvocation,
+    pub outcome: ToolOutcome,
+    /// time spent in the handler, zero when it did not run
+    pub elapsed: Duration,
+}
+
+/// Why a [`ToolRun`] stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToolRunStop {
+    /// the model answered without calling a function
+    Answered,
+    /// the model was still calling functions after the maximum number of iterations. Those last
+    /// calls are not run: each gets an error result, so the conversation can be sent again.
+    MaxIterations,
+}
+
+/// The outcome of [`ToolRunner::run`].
+#[derive(Debug)]
+pub struct ToolRun {
+    /// the last response of the model
+    pub response: ChatApiResponse,
+    /// every call, in the order the model requested them
+    pub transcript: Vec<ToolRecord>,
+    /// the number of requests sent
+    pub iterations: usize,
+    pub stop: ToolRunStop,
+}
+
+/// Drives the call loop of a [`Conversation`]: send it, run the functions the model asks for,
+/// append their results and send it again until the model answers.
+/// ```ignore
+/// let runner = ToolRunner::new(registry)
+///     .max_iterations(5)
+///     .approve(|call: ToolInvocation| async move { call.name != "delete_file" });
+/// let run = runner.send(&client, &mut conversation, "北京明天天气如何?").await?;
+/// println!("{}", conversation.last().unwrap());
+/// for record in &run.transcript {
+///     println!("{} -> {:?}", record.invocation.name, record.outcome);
+/// }
+/// ```
+#[derive(Clone)]
+pub struct ToolRunner {
+    registry: ToolRegistry,
+    max_iterations: usize,
+    parallel: bool,
+    validate: bool,
+    approval: Option<ApprovalHook>,
+}
+
+impl ToolRunner {
+    /// A runner allowing 8 iterations and running the calls of one reply concurrently.
+    pub fn new(registry: ToolRegistry) -> Self {
+        Self {
+            registry,
+            max_iterations: 8,
+            parallel: true,
+            validate: false,
+            approval: None,
+        }
+    }
+
+    /// set the maximum number of requests sent by one run
+    pub fn max_iterations(mut self, max_iterations: usize) -> Self {
+        self.max_iterations = max_iterations.max(1);
+        self
+    }
+
+    /// run the calls of one reply concurrently (the default) or one after the other
+    pub fn parallel(mut self, parallel: bool) -> Self {
+        self.parallel = parallel;
+        self
+    }
+
+    /// check the arguments against the declared [`Parameters`](super::Parameters) before calling the handler, a
+    /// mismatch is reported to the model as a failure
+    pub fn validate_arguments(mut self, validate: bool) -> Self {
+        self.validate = validate;
+        self
+    }
+
+    /// ask `hook` before every call, a call it refuses is reported to the model as denied
+    pub fn approve<F, Fut>(mut self, hook: F) -> Self
+    where
+        F: Fn(ToolInvocation) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = bool> + Send + 'static,
+    {
+        self.approval = Some(Arc::new(move |invocation| Box::pin(hook(invocation))));
+        self
+    }
+
+    pub fn registry(&self) -> &ToolRegistry {
+        &self.registry
+    }
+
+    /// Append a user message to `conversation`, then [`Self::run`] it. When the run fails, the
+    /// history is put back as it was, user message included.
+    pub async fn send(
+        &self,
+        client: &ZhipuClient,
+        conversation: &mut Conversation,
+        text: &str,
+    ) -> Result<ToolRun, ZhipuApiError> {
+        let history = conversation.history().to_vec();
+        conversation.push(Message::user(text));
+        let result = self.run(client, conversation).await;
+        if result.is_err() {
+            conversation.set_history(history);
+        }
+        result
+    }
+
+    /// Run the call loop on `conversation` as it is. Every reply of the model and every tool
+    /// result is appended to it. Failed or denied calls are reported to the model rather than
+    /// ending the run; only errors of the API do.
+    pub async fn run(
+        &self,
+        client: &ZhipuClient,
+        conversation: &mut Conversation,
+    ) -> Result<ToolRun, ZhipuApiError> {
+        let tools = self.registry.tools();
+        let mut transcript = Vec::new();
+        let mut iteration = 0;
+        loop {
+            iteration += 1;
+            let response = conversation.complete_with_tools(client, &tools).await?;
+            let invocations = conversation.last().map(invocations).unwrap_or_default();
+            let stop = if invocations.is_empty() {
+                Some(ToolRunStop::Answered)
+            } else if iteration >= self.max_iterations {
+                Some(ToolRunStop::MaxIterations)
+            } else {
+                None
+            };
+            if let Some(stop) = stop {
+                // every tool call needs a result, or the next request is rejected
+                for invocation in invocations {
+                    conversation.push(Message::tool_result(
+                        invocation.id.as_deref().unwrap_or_default(),
+                        "error: the call was not run, too many iterations",
+                    ));
+                }
+                return Ok(ToolRun {
+                    response,
+                    transcript,
+                    iterations: iteration,
+                    stop,
+                });
+            }
+            let records = if self.parallel {
+                join_all(
+                    invocations
+                        .into_iter()
+                        .map(|invocation| self.invoke(iteration, invocation)),
+                )
+                .await
+            } else {
+                let mut records = Vec::new();
+                for invocation in invocations {
+                    records.push(self.invoke(iteration, invocation).await);
+                }
+                records
+            };
+            for record in records {
+                conversation.push(Message::tool_result(
+                    record.invocation.id.as_deref().unwrap_or_default(),
+                    record.outcome.content(),
+                ));
+                transcript.push(record);
+            }
+        }
+    }
+
+    async fn invoke(&self, iteration: usize, invocation: ToolInvocation) -> ToolRecord {
+        let approved = match &self.approval {
+            Some(hook) => hook(invocation.clone()).await,
+            None => true,
+        };
+        let start = Instant::now();
+        let issues = self
+            .registry
+            .function(&invocation.name)
+            .filter(|_| self.validate)
+            .and_then(|function| function.parameters().check(&invocation.arguments).err());
+        let outcome = if !approved {
+            ToolOutcome::Denied
+        } else if let Some(issues) = issues {
+            ToolOutcome::Failure(issues.to_string())
+        } else {
+            match self
+                .registry
+                .call(&invocation.name, invocation.arguments.clone())
+                .await
+            {
+                Ok(content) => ToolOutcome::Success(content),
+                Err(e) => ToolOutcome::Failure(e.to_string()),
+            }
+        };
+        ToolRecord {
+            iteration,
+            elapsed: if approved {
+                start.elapsed()
+            } else {
+                Duration::ZERO
+            },
+            invocation,
+            outcome,
+        }
+    }
+}
+
+impl fmt::Debug for ToolRunner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ToolRunner")
+            .field("registry", &self.registry)
+            .field("max_iterations", &self.max_iterations)
+            .field("parallel", &self.parallel)
+            .field("validate", &self.validate)
+            .field("approval", &self.approval.is_some())
+            .finish()
+    }
+}
+
+/// the function calls of an assistant message
+fn invocations(message: &Message) -> Vec<ToolInvocation> {
+    message
+        .tool_calls()
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|call| {
+            let function = call.function()?;
+            Some(ToolInvocation {
+                id: call.id().map(str::to_string),
+                name: function.name().to_string(),
+                arguments: function.arguments().clone(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{Mock, MockResponse, MockServer, fixtures};
+    use crate::{api_resource::chat::Parameters, role::Role};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_runner_loops_until_answer() {
+        let server = MockServer::start().await.unwrap();
+        server
+            .mock(Mock::post(
+                "/chat/completions",
+                MockResponse::json(&fixtures::chat_tool_calls(&[
+                    ("call_1", "get_weather", json!({"city": "北京"})),
+                    ("call_2", "delete_file", json!({"path": "/"})),
+                ])),
+            ))
+            .mock(Mock::post(
+                "/chat/completions",
+                MockResponse::json(&fixtures::chat_completion("北京晴")),
+            ));
+        let registry = ToolRegistry::new()
+            .register(
+                Function::new("get_weather", "weather", Parameters::default()),
+                |arguments: Value| async move { Ok(format!("{} sunny", arguments["city"])) },
+            )
+            .register(
+                Function::new("delete_file", "delete", Parameters::default()),
+                |_| async move { Ok("deleted".to_string()) },
+            );
+        let runner = ToolRunner::new(registry)
+            .approve(|call: ToolInvocation| async move { call.name != "delete_file" });
+        let mut conversation = Conversation::new("glm-4-flash");
+
+        let run = runner
+            .send(&server.client(), &mut conversation, "天气")
+            .await
+            .unwrap();
+        assert_eq!(run.stop, ToolRunStop::Answered);
+        assert_eq!(run.iterations, 2);
+        assert_eq!(
+            run.transcript[0].outcome,
+            ToolOutcome::Success("\"北京\" sunny".to_string())
+        );
+        assert_eq!(run.transcript[1].outcome, ToolOutcome::Denied);
+
+        let sent = server.received()[1].json();
+        assert_eq!(sent["tools"].as_array().unwrap().len(), 2);
+        assert_eq!(sent["messages"][2]["role"], "tool");
+        assert_eq!(sent["messages"][2]["tool_call_id"], "call_1");
+        assert_eq!(
+            sent["messages"][3]["content"],
+            "error: the call was not approved"
+        );
+        assert_eq!(conversation.history().len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_runner_limits_and_failures_keep_the_history_valid() {
+        let server = MockServer::start().await.unwrap();
+        server
+            .mock(Mock::post(
+                "/chat/completions",
+                MockResponse::json(&fixtures::chat_tool_calls(&[(
+                    "call_1",
+                    "get_weather",
+                    json!({"city": "北京"}),
+                )])),
+            ))
+            .mock(Mock::post(
+                "/chat/completions",
+                MockResponse::error(400, "1214", "invalid request"),
+            ));
+        let registry = ToolRegistry::new().register(
+            Function::new("get_weather", "weather", Parameters::default()),
+            |_| async move { Ok("sunny".to_string()) },
+        );
+        let mut conversation = Conversation::new("glm-4-flash");
+
+        let run = ToolRunner::new(registry.clone())
+            .max_iterations(1)
+            .send(&server.client(), &mut conversation, "天气")
+            .await
+            .unwrap();
+        assert_eq!(run.stop, ToolRunStop::MaxIterations);
+        assert!(run.transcript.is_empty());
+        let pending = conversation.last().unwrap();
+        assert_eq!(pending.role(), Role::Tool);
+        assert_eq!(pending.tool_call_id(), Some("call_1"));
+
+        let error = ToolRunner::new(registry)
+            .send(&server.client(), &mut conversation, "明天呢")
+            .await;
+        assert!(error.is_err());
+        assert_eq!(conversation.history().len(), 3);
+        assert_eq!(conversation.last().unwrap().role(), Role::Tool);
+    }
+
+    /// Book rooms in a hotel
+    #[cfg(feature = "macros")]
+    #[crate::zhipu_tool(crate = crate)]
+    async fn book_hotel(
+        #[param(description = "the city")] city: String,
+        #[param(description = "the nights to stay")] nights: u32,
+        #[param(description = "wishes for the rooms")] wishes: Option<Vec<String>>,
+    ) -> Result<Vec<String>, std::io::Error> {
+        Ok(vec![
+            format!("{} x{}", city, nights);
+            wishes.map_or(1, |w| w.len())
+        ])
+    }
+
+    #[cfg(feature = "macros")]
+    #[tokio::test]
+    async fn test_zhipu_tool() {
+        let function = serde_json::to_value(book_hotel::function()).unwrap();
+        assert_eq!(function["name"], "book_hotel");
+        assert_eq!(function["description"], "Book rooms in a hotel");
+        assert_eq!(
+            function["parameters"]["required"],
+            json!(["city", "nights"])
+        );
+        assert_eq!(
+            function["parameters"]["properties"]["wishes"]["type"],
+            json!(["array", "null"])
+        );
+
+        let call: ToolCall = serde_json::from_value(json!({
+            "id": "call_1",
+            "type": "function",
+            "function": {"name": "book_hotel", "arguments": "{\"city\":\"北京\",\"nights\":2}"}
+        }))
+        .unwrap();
+        let content = book_hotel::dispatch(&call).await.unwrap().unwrap();
+        assert_eq!(content, r#"["北京 x2"]"#);
+        let registry = book_hotel::register(ToolRegistry::new());
+        assert!(
+            registry
+                .call("book_hotel", json!({"city": 1}))
+                .await
+                .is_err()
+        );
+    }
+}
@@ -1,8 +1,15 @@
-///! the images AI api of zhipu
-use super::data::*;
+//! the images AI api of zhipu
+use super::{data::*, model::ImagesModelName, response::ImagesChatApiResponse};
+use crate::{
+    api_resource::capability::model_info,
+    client::ZhipuClient,
+    endpoint::{Endpoint, EndpointConfig},
+    error::ZhipuApiError,
+};
+use log::warn;
 use serde::{Deserialize, Serialize};
 
-const API_URL: &str = "https://open.bigmodel.cn/api/paas/v4/images/generations";
+pub(crate) const API_PATH: &str = "/images/generations";
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[non_exhaustive]
@@ -20,8 +27,46 @@ pub struct ImagesApiRequest {
 }
 
 impl ImagesApiRequest {
-    pub fn to_json(&self) -> String {
-        serde_json::to_string(self).unwrap()
+    pub fn to_json(&self) -> Result<String, ZhipuApiError> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// the model the request is sent to
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// Check the model against the [`ModelInfo`](crate::api_resource::capability::ModelInfo)
+    /// table: a known chat or video model is refused. Custom model names pass.
+    pub fn validate(&self) -> Result<(), ZhipuApiError> {
+        let known_image_model = self
+            .model
+            .parse::<ImagesModelName>()
+            .is_ok_and(|m| m.info().is_some());
+        if !known_image_model && model_info(&self.model).is_some() {
+            return Err(ZhipuApiError::InvalidRequest(format!(
+                "{} is not an image model",
+                self.model
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl Endpoint for ImagesApiRequest {
+    type Response = ImagesChatApiResponse;
+    const NAME: &'static str = "images";
+
+    fn path(&self) -> String {
+        API_PATH.to_string()
+    }
+
+    fn body(&self) -> Result<Option<String>, ZhipuApiError> {
+        self.to_json().map(Some)
+    }
+
+    fn model(&self) -> Option<&str> {
+        Some(&self.model)
     }
 }
 
@@ -34,6 +79,8 @@ pub struct ImagesApiRequestBuilder {
     size: Option<ImageSize>,
     /// user id
     user_id: Option<String>,
+    /// endpoint, [`EndpointConfig::global`] when `None`
+    endpoint: Option<EndpointConfig>,
 }
 
 impl ImagesApiRequestBuilder {
@@ -51,6 +98,8 @@ impl ImagesApiRequestBuilder {
             size: None,
             // Initialize the `user_id` field as `None`, indicating no user ID is set initially.
             user_id: None,
+            // Initialize the `endpoint` field as `None`, so the global endpoint is used.
+            endpoint: None,
         }
     }
     // Define a public method named `prompt` which takes a mutable reference to `self` and a string slice `prompt` as arguments.
@@ -83,13 +132,35 @@ impl ImagesApiRequestBuilder {
         // Return the modified instance of the struct.
         self
     }
-    // Define a public function named `build` that consumes the instance (`self`) of the struct it's defined in.
+    // Set the endpoint the request is sent to, instead of the global one.
+    pub fn endpoint(mut self, endpoint: EndpointConfig) -> Self {
+        self.endpoint = Some(endpoint);
+        self
+    }
+    /// Build the request, logging a warning when the model does not support it,
+    /// see [`ImagesApiRequest::validate`].
     pub fn build(self) -> (String, ImagesApiRequest) {
+        let (api_url, request) = self.build_unchecked();
+        if let Err(e) = request.validate() {
+            warn!("{}", e);
+        }
+        (api_url, request)
+    }
+    /// Build the request, failing when the model does not support it,
+    /// see [`ImagesApiRequest::validate`].
+    pub fn try_build(self) -> Result<(String, ImagesApiRequest), ZhipuApiError> {
+        let (api_url, request) = self.build_unchecked();
+        request.validate()?;
+        Ok((api_url, request))
+    }
+    // Define a function named `build_unchecked` that consumes the instance (`self`) of the struct it's defined in.
+    fn build_unchecked(self) -> (String, ImagesApiRequest) {
+        let endpoint = self.endpoint.unwrap_or_else(EndpointConfig::global);
         // Return a tuple containing two elements:
-        // 1. A new `String` created from the constant `API_URL`.
+        // 1. The URL of `API_PATH` on the endpoint.
         // 2. The current instance (`self`) of the struct, cast to `ImagesApiRequest`.
         (
-            API_URL.to_string(),
+            endpoint.url(API_PATH),
             ImagesApiRequest {
                 model: self.model,
                 prompt: self.prompt,
@@ -98,4 +169,38 @@ impl ImagesApiRequestBuilder {
             },
         )
     }
+    // Build the request and send it through `client`, decoding the generated images.
+    // The request goes to the endpoint of the client, the one set with `endpoint` is ignored.
+    pub async fn send(self, client: &ZhipuClient) -> Result<ImagesChatApiResponse, ZhipuApiError> {
+        // Drop the URL, the client knows where to send the request.
+        let (_, request) = self.try_build()?;
+        client.images(&request).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_against_model_capabilities() {
+        assert!(
+            ImagesApiRequestBuilder::new("cogview-4")
+                .try_build()
+                .is_ok()
+        );
+        assert!(
+            ImagesApiRequestBuilder::new("my-image-model")
+                .try_build()
+                .is_ok()
+        );
+        let error = ImagesApiRequestBuilder::new("cogvideox-3")
+            .try_build()
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "ZhipuApiError: cogvideox-3 is not an image model"
+        );
+    }
 }
@@ -1,5 +1,5 @@
-///! response of chat api
-use crate::error::ZhipuApiError;
+//! response of chat api
+use crate::{endpoint::decode_response, error::ZhipuApiError};
 use reqwest::Response;
 use serde::{Deserialize, Serialize};
 
@@ -33,14 +33,5 @@ pub struct ContentFilter {
 pub async fn images_response_context(
     response: Response,
 ) -> Result<ImagesChatApiResponse, ZhipuApiError> {
-    if response.status().is_success() {
-        let response = response.text().await?;
-        let response: ImagesChatApiResponse = serde_json::from_str(&response)?;
-        Ok(response)
-    } else {
-        Err(ZhipuApiError::StatusCode(format!(
-            "Failed to fetch data: {}",
-            response.status()
-        )))
-    }
+    decode_response(response).await
 }
@@ -1,7 +1,9 @@
 pub mod builder;
+pub mod capability;
 pub mod chat;
 pub mod images;
 pub mod rtav;
+pub mod tokenizer;
 pub mod videos;
 
 use builder::Builder;
@@ -15,7 +17,10 @@ impl<T> BigModel<T>
 where
     T: Builder,
 {
-    pub fn new(name: &str) -> T::Item {
-        T::new(name)
+    /// Start a request builder for the model `name`, a string or a model name enum such as
+    /// [`chat::ChatModelName`].
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(name: impl AsRef<str>) -> T::Item {
+        T::new(name.as_ref())
     }
 }
@@ -5,7 +5,7 @@
 mod event;
 mod value;
 
-use crate::error::ZhipuApiError;
+use crate::{auth::Auth, endpoint::EndpointConfig, error::ZhipuApiError, telemetry::SessionSpan};
 use futures::{
     Sink, SinkExt, Stream, StreamExt,
     stream::{SplitSink, SplitStream},
@@ -25,6 +25,7 @@ pub use {event::*, value::*};
 #[pin_project]
 pub struct SessionSink {
     inner: SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>,
+    span: SessionSpan,
 }
 
 impl SessionSink {
@@ -46,8 +47,8 @@ impl SessionSink {
     ///
     /// # 参数
     /// * `audio`: 仅支持wav格式，默认采样率为16000；
-    /// 如需自定义采样率，可在参数中标注，wav48表示48000hz采样率；
-    /// 建议使用16000、24000、48000hz；
+    ///   如需自定义采样率，可在参数中标注，wav48表示48000hz采样率；
+    ///   建议使用16000、24000、48000hz；
     pub async fn input_audio_buffer_append(&mut self, audio: &[u8]) -> Result<(), ZhipuApiError> {
         self.send(Event::new_input_audio_buffer_append(audio)?)
             .await
@@ -121,7 +122,9 @@ impl Sink<Event> for SessionSink {
     }
 
     fn start_send(self: Pin<&mut Self>, item: Event) -> Result<(), Self::Error> {
-        Pin::new(&mut self.project().inner)
+        let this = self.project();
+        this.span.sent(item.event_type());
+        Pin::new(this.inner)
             .start_send(item.into_message()?)
             .map_err(|e| e.into())
     }
@@ -142,6 +145,7 @@ impl Sink<Event> for SessionSink {
 #[pin_project]
 pub struct SessionStream {
     inner: SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>,
+    span: SessionSpan,
 }
 
 impl SessionStream {
@@ -157,9 +161,10 @@ impl Stream for SessionStream {
     type Item = Result<Event, ZhipuApiError>;
 
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        Pin::new(&mut self.project().inner)
-            .poll_next(cx)
-            .map(|o| match o {
+        let this = self.project();
+        let span = this.span;
+        Pin::new(this.inner).poll_next(cx).map(|o| {
+            let item = match o {
                 None => None,
                 Some(o) => o.map_or_else(
                     |e| Some(Err(e.into())),
@@ -171,18 +176,46 @@ impl Stream for SessionStream {
                         }
                     },
                 ),
-            })
+            };
+            match &item {
+                None => span.closed(),
+                Some(Ok(event)) => span.received(event.event_type()),
+                Some(Err(e)) => span.failed(e),
+            }
+            item
+        })
     }
 }
 
-const URL: &str = "wss://open.bigmodel.cn/api/paas/v4/realtime";
-
+/// 连接到 [`EndpointConfig::global`] 的实时接口
 pub async fn start_realtime_session(
     api_key: &str,
 ) -> Result<(SessionSink, SessionStream), ZhipuApiError> {
-    let mut req = URL.into_client_request()?;
+    connect_realtime_session(EndpointConfig::global().realtime_url(), api_key).await
+}
+
+/// Same as [`start_realtime_session`], authenticating the handshake with `auth`.
+pub async fn start_realtime_session_with_auth(
+    auth: &Auth,
+) -> Result<(SessionSink, SessionStream), ZhipuApiError> {
+    start_realtime_session_with(&EndpointConfig::global(), auth).await
+}
+
+/// Same as [`start_realtime_session_with_auth`], connecting to the realtime URL of `endpoint`.
+pub async fn start_realtime_session_with(
+    endpoint: &EndpointConfig,
+    auth: &Auth,
+) -> Result<(SessionSink, SessionStream), ZhipuApiError> {
+    connect_realtime_session(endpoint.realtime_url(), &auth.bearer_token()?).await
+}
+
+pub(crate) async fn connect_realtime_session(
+    url: &str,
+    bearer_token: &str,
+) -> Result<(SessionSink, SessionStream), ZhipuApiError> {
+    let mut req = url.into_client_request()?;
     req.headers_mut()
-        .insert("Authorization", format!("Bearer {}", api_key).parse()?);
+        .insert("Authorization", format!("Bearer {}", bearer_token).parse()?);
 
     // 连接到WebSocket服务器
     let (stream, response) = connect_async(req).await?;
@@ -190,9 +223,21 @@ pub async fn start_realtime_session(
     // 这通常是HTTP升级响应
     let status = response.status();
     if StatusCode::SWITCHING_PROTOCOLS != status && !status.is_success() {
-        return Err(ZhipuApiError::StatusCode(status.to_string()));
+        return Err(ZhipuApiError::Api {
+            status: status.as_u16(),
+            code: None,
+            message: format!("WebSocket handshake failed: {}", status),
+            request_id: None,
+        });
     }
 
+    let span = SessionSpan::new(url);
     let (send, recv) = stream.split();
-    Ok((SessionSink { inner: send }, SessionStream { inner: recv }))
+    Ok((
+        SessionSink {
+            inner: send,
+            span: span.clone(),
+        },
+        SessionStream { inner: recv, span },
+    ))
 }
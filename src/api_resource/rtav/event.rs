@@ -53,6 +53,11 @@ impl Event {
         }
     }
 
+    /// 获取事件类型
+    pub fn event_type(&self) -> Option<&str> {
+        self.data.get("type").and_then(Value::as_str)
+    }
+
     /// 获取客户端时间戳
     /// 调用端发起调用的时间戳，毫秒
     pub fn client_timestamp(&self) -> u64 {
@@ -79,8 +84,8 @@ impl Event {
     ///
     /// # 参数
     /// * `audio`: 仅支持wav格式，默认采样率为16000；
-    /// 如需自定义采样率，可在参数中标注，wav48表示48000hz采样率；
-    /// 建议使用16000、24000、48000hz；
+    ///   如需自定义采样率，可在参数中标注，wav48表示48000hz采样率；
+    ///   建议使用16000、24000、48000hz；
     pub fn new_input_audio_buffer_append(audio: &[u8]) -> Result<Self, ZhipuApiError> {
         let mut data = HashMap::new();
         let audio = BASE64_STANDARD.encode(audio);
@@ -173,6 +173,12 @@ pub struct TurnDetection {
     r#type: VadType,
 }
 
+impl Default for TurnDetection {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl TurnDetection {
     pub fn new() -> Self {
         Self {
@@ -264,6 +270,12 @@ pub struct Session {
     pub beta_fields: BetaFields,
 }
 
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Session {
     /// 创建事件会话
     pub fn new() -> Self {
@@ -349,12 +361,12 @@ impl Session {
     ///
     /// # 参数
     /// * `voice`:
-    /// 默认女声:tongtong.
-    /// 甜美女性：female-tianmei
-    /// 青年大学生：male-qn-daxuesheng.
-    /// 精英青年：male-qn-jingying.
-    /// 萌萌女童：lovely_girl.
-    /// 少女：female-shaonv
+    ///   默认女声:tongtong.
+    ///   甜美女性：female-tianmei
+    ///   青年大学生：male-qn-daxuesheng.
+    ///   精英青年：male-qn-jingying.
+    ///   萌萌女童：lovely_girl.
+    ///   少女：female-shaonv
     pub fn with_voice(&mut self, voice: &str) -> &mut Self {
         self.voice = Some(voice.to_owned());
         self
@@ -400,6 +412,7 @@ impl Session {
     ///
     /// # 参数
     /// * `tools`: ServerVAD 时，更新tools要同时传入turn_detection。当前仅audio模式支持tools调用。
+    ///   参数同对话接口，可用 `Parameters::from_type`（`schemars` feature）从 Rust 类型生成。
     pub fn with_tools(&mut self, tools: &[super::super::chat::Function]) -> &mut Self {
         self.tools = Some(tools.to_vec());
         self
@@ -517,6 +530,12 @@ pub struct ConversationItem {
     pub output: Option<String>,
 }
 
+impl Default for ConversationItem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ConversationItem {
     fn serialize_contents<S>(contents: &[ContentPart], serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -592,7 +611,7 @@ impl ConversationItem {
         D: Deserializer<'de>,
     {
         let role = Option::<String>::deserialize(deserializer)?;
-        Ok(role.map_or(None, |i| Some(i.as_str().into())))
+        Ok(role.map(|i| i.as_str().into()))
     }
 
     pub fn new() -> Self {
@@ -0,0 +1,153 @@
+//! the tokenizer api of zhipu, counting the tokens of a prompt without running the model
+use super::response::TokenizerApiResponse;
+use crate::{
+    api_resource::chat::{ChatApiRequest, Message, Messages, Tool},
+    client::ZhipuClient,
+    endpoint::{Endpoint, EndpointConfig},
+    error::ZhipuApiError,
+};
+use serde::{Deserialize, Serialize};
+
+pub(crate) const API_PATH: &str = "/tokenizer";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[non_exhaustive]
+pub struct TokenizerApiRequest {
+    /// the model whose tokenizer is used
+    model: String,
+    /// the messages to count, in the same form as a chat request
+    messages: Vec<Message>,
+    /// the tools offered to the model, they count towards the prompt too
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tools: Option<Vec<Tool>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    user_id: Option<String>,
+}
+
+impl TokenizerApiRequest {
+    pub fn to_json(&self) -> Result<String, ZhipuApiError> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// the model the request is sent to
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+}
+
+impl From<&ChatApiRequest> for TokenizerApiRequest {
+    /// Count the prompt of a chat request: its model, messages and tools.
+    fn from(request: &ChatApiRequest) -> Self {
+        Self {
+            model: request.model().to_string(),
+            messages: request.messages().to_vec(),
+            tools: request.tools().map(<[Tool]>::to_vec),
+            request_id: None,
+            user_id: None,
+        }
+    }
+}
+
+impl Endpoint for TokenizerApiRequest {
+    type Response = TokenizerApiResponse;
+    const NAME: &'static str = "tokenizer";
+
+    fn path(&self) -> String {
+        API_PATH.to_string()
+    }
+
+    fn body(&self) -> Result<Option<String>, ZhipuApiError> {
+        self.to_json().map(Some)
+    }
+
+    fn model(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
+    fn request_id(&self) -> Option<&str> {
+        self.request_id.as_deref()
+    }
+}
+
+/// Builder of [`TokenizerApiRequest`].
+/// ```ignore
+/// let response = BigModel::<Tokenizer>::new("glm-4-plus")
+///     .add_message(chat_simple_message!(Role::User, "hello"))
+///     .send(&client)
+///     .await?;
+/// println!("{} tokens", response.total_tokens());
+/// ```
+pub struct TokenizerApiRequestBuilder {
+    model: String,
+    messages: Vec<Message>,
+    tools: Option<Vec<Tool>>,
+    request_id: Option<String>,
+    user_id: Option<String>,
+    endpoint: Option<EndpointConfig>,
+}
+
+impl TokenizerApiRequestBuilder {
+    pub fn new(model: &str) -> Self {
+        Self {
+            model: model.to_string(),
+            messages: Vec::new(),
+            tools: None,
+            request_id: None,
+            user_id: None,
+            endpoint: None,
+        }
+    }
+
+    pub fn add_message(mut self, message: Message) -> Self {
+        self.messages.push(message);
+        self
+    }
+
+    pub fn add_messages(mut self, messages: Messages) -> Self {
+        self.messages.extend(messages.messages);
+        self
+    }
+
+    pub fn add_tool(mut self, tool: Tool) -> Self {
+        self.tools.get_or_insert_with(Vec::new).push(tool);
+        self
+    }
+
+    pub fn request_id(mut self, request_id: &str) -> Self {
+        self.request_id = Some(request_id.to_string());
+        self
+    }
+
+    pub fn user_id(mut self, user_id: &str) -> Self {
+        self.user_id = Some(user_id.to_string());
+        self
+    }
+
+    /// set the endpoint the request is sent to, [`EndpointConfig::global`] when not set
+    pub fn endpoint(mut self, endpoint: EndpointConfig) -> Self {
+        self.endpoint = Some(endpoint);
+        self
+    }
+
+    pub fn build(self) -> (String, TokenizerApiRequest) {
+        let endpoint = self.endpoint.unwrap_or_else(EndpointConfig::global);
+        (
+            endpoint.url(API_PATH),
+            TokenizerApiRequest {
+                model: self.model,
+                messages: self.messages,
+                tools: self.tools,
+                request_id: self.request_id,
+                user_id: self.user_id,
+            },
+        )
+    }
+
+    /// Build the request and send it through `client`, on the endpoint of the client.
+    pub async fn send(self, client: &ZhipuClient) -> Result<TokenizerApiResponse, ZhipuApiError> {
+        let (_, request) = self.build();
+        client.tokenize(&request).await
+    }
+}
@@ -0,0 +1,123 @@
+//! # Offline token estimates
+//!
+//! A rough count of the tokens a prompt consumes, for budget checks and history trimming where a
+//! call to the tokenizer API would be too slow. Text counts one token per CJK character and one
+//! per four other characters; images, videos and audio clips count a fixed amount each. Use
+//! [`TokenizerApiRequest`](super::TokenizerApiRequest) when the exact number matters.
+
+use crate::api_resource::chat::{ChatApiRequest, Context, Message, Messages, RichContent};
+
+/// the tokens counted for one image
+pub const IMAGE_TOKENS: u32 = 1_600;
+/// the tokens counted for one video
+pub const VIDEO_TOKENS: u32 = 8_000;
+/// the tokens counted for one audio clip
+pub const AUDIO_TOKENS: u32 = 1_000;
+/// the tokens counted for the role and separators of every message
+pub const MESSAGE_OVERHEAD_TOKENS: u32 = 4;
+
+/// Estimate the tokens of a prompt without a network call.
+/// ```ignore
+/// let (_, request) = builder.build();
+/// if request.estimated_tokens() > 120_000 {
+///     conversation.trim();
+/// }
+/// ```
+pub trait EstimateTokens {
+    fn estimated_tokens(&self) -> u32;
+}
+
+impl EstimateTokens for str {
+    fn estimated_tokens(&self) -> u32 {
+        let (cjk, other) = self.chars().fold((0u32, 0u32), |(cjk, other), c| {
+            if c as u32 >= 0x2E80 {
+                (cjk + 1, other)
+            } else {
+                (cjk, other + 1)
+            }
+        });
+        cjk + other.div_ceil(4)
+    }
+}
+
+impl EstimateTokens for RichContent {
+    fn estimated_tokens(&self) -> u32 {
+        match self.get_type() {
+            "image_url" => IMAGE_TOKENS,
+            "video_url" => VIDEO_TOKENS,
+            "input_audio" => AUDIO_TOKENS,
+            _ => self.get_text().map_or(0, str::estimated_tokens),
+        }
+    }
+}
+
+impl EstimateTokens for Context {
+    fn estimated_tokens(&self) -> u32 {
+        match self {
+            Context::SimpleContexts(text) => text.estimated_tokens(),
+            Context::RichContents(contents) => contents.iter().map(|c| c.estimated_tokens()).sum(),
+        }
+    }
+}
+
+impl EstimateTokens for Message {
+    fn estimated_tokens(&self) -> u32 {
+        let tool_calls = self
+            .tool_calls()
+            .and_then(|calls| serde_json::to_string(calls).ok())
+            .map_or(0, |calls| calls.estimated_tokens());
+        MESSAGE_OVERHEAD_TOKENS
+            + self.content().map_or(0, Context::estimated_tokens)
+            + self.reasoning_content().map_or(0, str::estimated_tokens)
+            + tool_calls
+    }
+}
+
+impl EstimateTokens for [Message] {
+    fn estimated_tokens(&self) -> u32 {
+        self.iter().map(Message::estimated_tokens).sum()
+    }
+}
+
+impl EstimateTokens for Messages {
+    fn estimated_tokens(&self) -> u32 {
+        self.messages.estimated_tokens()
+    }
+}
+
+impl EstimateTokens for ChatApiRequest {
+    /// the messages and the tool definitions, not the completion
+    fn estimated_tokens(&self) -> u32 {
+        let tools = self
+            .tools()
+            .and_then(|tools| serde_json::to_string(tools).ok())
+            .map_or(0, |tools| tools.estimated_tokens());
+        self.messages().estimated_tokens() + tools
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_text() {
+        assert_eq!("".estimated_tokens(), 0);
+        assert_eq!("hello world!".estimated_tokens(), 3);
+        assert_eq!("你好".estimated_tokens(), 2);
+    }
+
+    #[test]
+    fn test_estimate_mixed_content() {
+        let messages = Messages::new()
+            .add_message(Message::system("你好世界"))
+            .add_message(Message::user(
+                Context::rich_contexts(RichContent::text("describe this"))
+                    .rich_content(RichContent::image_url("https://example.com/cat.png")),
+            ));
+        assert_eq!(
+            messages.estimated_tokens(),
+            2 * MESSAGE_OVERHEAD_TOKENS + 4 + 4 + IMAGE_TOKENS
+        );
+    }
+}
@@ -0,0 +1,18 @@
+pub mod api;
+pub mod estimate;
+pub mod response;
+
+pub use api::*;
+pub use estimate::*;
+pub use response::*;
+
+use super::builder::Builder;
+
+pub type Tokenizer = TokenizerApiRequestBuilder;
+
+impl Builder for Tokenizer {
+    type Item = Tokenizer;
+    fn new(model_name: &str) -> Self::Item {
+        Tokenizer::new(model_name)
+    }
+}
@@ -0,0 +1,65 @@
+//! response of tokenizer api
+use crate::{endpoint::decode_response, error::ZhipuApiError};
+use reqwest::Response;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TokenizerApiResponse {
+    id: Option<String>,
+    created: Option<u64>,
+    request_id: Option<String>,
+    usage: TokenizerUsage,
+}
+
+impl TokenizerApiResponse {
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+    pub fn created(&self) -> Option<u64> {
+        self.created
+    }
+    pub fn request_id(&self) -> Option<&str> {
+        self.request_id.as_deref()
+    }
+    pub fn usage(&self) -> &TokenizerUsage {
+        &self.usage
+    }
+    /// the number of tokens of the whole prompt
+    pub fn total_tokens(&self) -> u32 {
+        self.usage.total_tokens
+    }
+}
+
+/// The tokens the prompt would consume, by kind of input.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenizerUsage {
+    #[serde(default)]
+    prompt_tokens: u32,
+    #[serde(default)]
+    image_tokens: u32,
+    #[serde(default)]
+    video_tokens: u32,
+    total_tokens: u32,
+}
+
+impl TokenizerUsage {
+    /// tokens of the text
+    pub fn prompt_tokens(&self) -> u32 {
+        self.prompt_tokens
+    }
+    pub fn image_tokens(&self) -> u32 {
+        self.image_tokens
+    }
+    pub fn video_tokens(&self) -> u32 {
+        self.video_tokens
+    }
+    pub fn total_tokens(&self) -> u32 {
+        self.total_tokens
+    }
+}
+
+pub async fn tokenizer_response_context(
+    response: Response,
+) -> Result<TokenizerApiResponse, ZhipuApiError> {
+    decode_response(response).await
+}
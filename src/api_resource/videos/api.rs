@@ -1,8 +1,16 @@
-use super::data::*;
+use super::{data::*, model::VideosModelName, response::*};
+use crate::{
+    api_resource::capability::{Feature, model_info},
+    client::ZhipuClient,
+    endpoint::{Endpoint, EndpointConfig},
+    error::ZhipuApiError,
+};
+use log::warn;
+use reqwest::Method;
 use serde::{Deserialize, Serialize};
 
-const API_URL: &str = "https://open.bigmodel.cn/api/paas/v4/videos/generations";
-const API_URL_ASYNC: &str = "https://open.bigmodel.cn/api/paas/v4/async-result";
+pub(crate) const API_PATH: &str = "/videos/generations";
+pub(crate) const API_PATH_ASYNC: &str = "/async-result";
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[non_exhaustive]
@@ -37,8 +45,72 @@ pub struct VideosApiRequest {
     user_id: Option<String>,
 }
 impl VideosApiRequest {
-    pub fn to_json(&self) -> String {
-        serde_json::to_string(self).unwrap()
+    pub fn to_json(&self) -> Result<String, ZhipuApiError> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// the model the request is sent to
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// the request id, if one was set
+    pub fn request_id(&self) -> Option<&str> {
+        self.request_id.as_deref()
+    }
+
+    /// set a generated request id when none was provided, so retries share the same id
+    pub(crate) fn ensure_request_id(&mut self, generate: impl FnOnce() -> String) {
+        self.request_id.get_or_insert_with(generate);
+    }
+
+    /// Check the request against the [`ModelInfo`](crate::api_resource::capability::ModelInfo)
+    /// of its model: a known chat or image model is refused, and an `image_url` needs image
+    /// input. Requests for custom models pass these checks.
+    pub fn validate(&self) -> Result<(), ZhipuApiError> {
+        let Some(info) = self
+            .model
+            .parse::<VideosModelName>()
+            .ok()
+            .and_then(|m| m.info())
+        else {
+            if model_info(&self.model).is_some() {
+                return Err(ZhipuApiError::InvalidRequest(format!(
+                    "{} is not a video model",
+                    self.model
+                )));
+            }
+            return Ok(());
+        };
+        if self.image_url.is_some() && !info.supports(Feature::VisionInput) {
+            return Err(ZhipuApiError::InvalidRequest(format!(
+                "{} does not support {}",
+                self.model,
+                Feature::VisionInput
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl Endpoint for VideosApiRequest {
+    type Response = VideosChatApiResponse;
+    const NAME: &'static str = "videos";
+
+    fn path(&self) -> String {
+        API_PATH.to_string()
+    }
+
+    fn body(&self) -> Result<Option<String>, ZhipuApiError> {
+        self.to_json().map(Some)
+    }
+
+    fn model(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
+    fn request_id(&self) -> Option<&str> {
+        self.request_id.as_deref()
     }
 }
 
@@ -65,6 +137,8 @@ pub struct VideosApiRequestBuilder {
     user_id: Option<String>,
     /// response_id
     response_id: String,
+    /// endpoint, [`EndpointConfig::global`] when `None`
+    endpoint: Option<EndpointConfig>,
 }
 
 impl VideosApiRequestBuilder {
@@ -96,6 +170,8 @@ impl VideosApiRequestBuilder {
             user_id: None,
             // Initialize the `response_id` field as `None`, indicating no response ID is set initially.
             response_id: String::new(),
+            // Initialize the `endpoint` field as `None`, so the global endpoint is used.
+            endpoint: None,
         }
     }
 
@@ -179,12 +255,38 @@ impl VideosApiRequestBuilder {
         self
     }
 
+    pub fn endpoint(mut self, endpoint: EndpointConfig) -> Self {
+        // Use the given endpoint instead of the global one.
+        self.endpoint = Some(endpoint);
+        // Return the modified instance of the struct.
+        self
+    }
+
+    /// Build the request, logging a warning when the model does not support it,
+    /// see [`VideosApiRequest::validate`].
     pub fn build(self) -> (String, VideosApiRequest) {
+        let (api_url, request) = self.build_unchecked();
+        if let Err(e) = request.validate() {
+            warn!("{}", e);
+        }
+        (api_url, request)
+    }
+
+    /// Build the request, failing when the model does not support it,
+    /// see [`VideosApiRequest::validate`].
+    pub fn try_build(self) -> Result<(String, VideosApiRequest), ZhipuApiError> {
+        let (api_url, request) = self.build_unchecked();
+        request.validate()?;
+        Ok((api_url, request))
+    }
+
+    fn build_unchecked(self) -> (String, VideosApiRequest) {
+        let endpoint = self.endpoint.unwrap_or_else(EndpointConfig::global);
         // Return a tuple containing two elements:
-        // 1. A new `String` created from the constant `API_URL`.
+        // 1. The URL of `API_PATH` on the endpoint.
         // 2. The current instance (`self`) of the struct, cast to `VideosApiRequest`.
         (
-            API_URL.to_string(), // Convert the constant to a `String` type.
+            endpoint.url(API_PATH),
             VideosApiRequest {
                 model: self.model,
                 prompt: self.prompt,
@@ -200,8 +302,16 @@ impl VideosApiRequestBuilder {
         )
     }
 
+    pub async fn send(self, client: &ZhipuClient) -> Result<VideosChatApiResponse, ZhipuApiError> {
+        // Build the request and submit the task through `client`, on the endpoint of the client.
+        let (_, request) = self.try_build()?;
+        // Poll the task with `client.videos_result` and the id of the response.
+        client.videos(&request).await
+    }
+
     pub fn build_response(self) -> (String, VideosApiAsynRequest) {
-        let api_url = format!("{}/{}", API_URL_ASYNC, self.response_id);
+        let endpoint = self.endpoint.unwrap_or_else(EndpointConfig::global);
+        let api_url = format!("{}/{}", endpoint.url(API_PATH_ASYNC), self.response_id);
         (
             api_url,
             VideosApiAsynRequest {
@@ -219,7 +329,53 @@ pub struct VideosApiAsynRequest {
 }
 
 impl VideosApiAsynRequest {
-    pub fn to_json(&self) -> String {
-        serde_json::to_string(self).unwrap()
+    /// query the result of the task with id `task_id`
+    pub fn new(task_id: &str) -> Self {
+        Self {
+            id: task_id.to_string(),
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, ZhipuApiError> {
+        Ok(serde_json::to_string(self)?)
+    }
+}
+
+impl Endpoint for VideosApiAsynRequest {
+    type Response = VideosChatApiAsynResponse;
+    const NAME: &'static str = "videos_result";
+
+    fn method(&self) -> Method {
+        Method::GET
+    }
+
+    fn path(&self) -> String {
+        format!("{}/{}", API_PATH_ASYNC, self.id)
+    }
+
+    fn body(&self) -> Result<Option<String>, ZhipuApiError> {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_against_model_capabilities() {
+        let builder = VideosApiRequestBuilder::new("cogvideox-3").image_url("https://a.b/c.png");
+        assert!(builder.try_build().is_ok());
+        let error = VideosApiRequestBuilder::new("glm-4-flash")
+            .prompt("a cat")
+            .try_build()
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "ZhipuApiError: glm-4-flash is not a video model"
+        );
+        let custom = VideosApiRequestBuilder::new("my-video-model").image_url("https://a.b/c.png");
+        assert!(custom.try_build().is_ok());
     }
 }
@@ -1,31 +1,20 @@
-//! # CogView model name
+//! # CogVideoX model name
 
-use std::fmt;
+use crate::api_resource::capability::{ModelInfo, model_names};
 
-#[derive(Debug, PartialEq, Eq, Clone)]
-#[non_exhaustive]
-pub enum VideosModelName {
-    Cogvideox3,
-    Cogvideox2,
-    CogvideoxFlash,
-}
-
-impl fmt::Display for VideosModelName {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Self::Cogvideox3 => write!(f, "cogvideox-3"),
-            Self::Cogvideox2 => write!(f, "cogvideox-2"),
-            Self::CogvideoxFlash => write!(f, "cogvideox-flash"),
-        }
+model_names! {
+    #[non_exhaustive]
+    pub enum VideosModelName {
+        Cogvideox3 => "cogvideox-3",
+        Cogvideox2 => "cogvideox-2",
+        CogvideoxFlash => "cogvideox-flash",
     }
 }
 
-impl From<VideosModelName> for &'static str {
-    fn from(model: VideosModelName) -> &'static str {
-        match model {
-            VideosModelName::Cogvideox3 => "cogvideox-3",
-            VideosModelName::Cogvideox2 => "cogvideox-2",
-            VideosModelName::CogvideoxFlash => "cogvideox-flash",
-        }
+impl VideosModelName {
+    /// The capabilities of the model, `None` for [`Self::Custom`]. Every model can start from an
+    /// image.
+    pub fn info(&self) -> Option<ModelInfo> {
+        Some(ModelInfo::media(self.static_name()?).vision())
     }
 }
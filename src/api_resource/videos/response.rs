@@ -1,5 +1,5 @@
-///! response of chat api
-use crate::error::ZhipuApiError;
+//! response of chat api
+use crate::{endpoint::decode_response, error::ZhipuApiError};
 use reqwest::Response;
 use serde::{Deserialize, Serialize};
 
@@ -32,16 +32,7 @@ impl VideosChatApiResponse {
 pub async fn videos_response_context(
     response: Response,
 ) -> Result<VideosChatApiResponse, ZhipuApiError> {
-    if response.status().is_success() {
-        let response = response.text().await?;
-        let response: VideosChatApiResponse = serde_json::from_str(&response)?;
-        Ok(response)
-    } else {
-        Err(ZhipuApiError::StatusCode(format!(
-            "Failed to fetch data: {}",
-            response.status()
-        )))
-    }
+    decode_response(response).await
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -85,14 +76,5 @@ impl Video {
 pub async fn videos_asyn_response_context(
     response: Response,
 ) -> Result<VideosChatApiAsynResponse, ZhipuApiError> {
-    if response.status().is_success() {
-        let response = response.text().await?;
-        let response: VideosChatApiAsynResponse = serde_json::from_str(&response)?;
-        Ok(response)
-    } else {
-        Err(ZhipuApiError::StatusCode(format!(
-            "Failed to fetch data: {}",
-            response.status()
-        )))
-    }
+    decode_response(response).await
 }
@@ -0,0 +1,206 @@
+//! # Authentication strategies
+//!
+//! The platform accepts either the raw API key or a short-lived HS256 JWT signed from the
+//! `{id}.{secret}` API key. With [`Auth::Jwt`] the secret never leaves the process.
+
+use crate::error::ZhipuApiError;
+use base64::prelude::*;
+use hmac::{Hmac, Mac};
+use serde_json::json;
+use sha2::Sha256;
+use std::{
+    fmt::{Debug, Formatter},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Default lifetime of a signed token.
+pub const DEFAULT_JWT_TTL: Duration = Duration::from_secs(3 * 60);
+/// Default time before expiry at which a cached token is replaced.
+pub const DEFAULT_JWT_REFRESH_MARGIN: Duration = Duration::from_secs(30);
+
+/// How requests are authenticated.
+#[derive(Clone)]
+pub enum Auth {
+    /// Send the API key as-is in `Authorization: Bearer`.
+    ApiKey(String),
+    /// Send a JWT signed from the API key.
+    Jwt(JwtAuth),
+}
+
+impl Auth {
+    /// Authenticate with the raw API key.
+    pub fn api_key(api_key: &str) -> Self {
+        Self::ApiKey(api_key.to_string())
+    }
+
+    /// Authenticate with JWTs signed from an `{id}.{secret}` API key, using [`DEFAULT_JWT_TTL`].
+    pub fn jwt(api_key: &str) -> Result<Self, ZhipuApiError> {
+        Ok(Self::Jwt(JwtAuth::new(api_key)?))
+    }
+
+    /// The value placed after `Bearer ` in the `Authorization` header.
+    pub fn bearer_token(&self) -> Result<String, ZhipuApiError> {
+        match self {
+            Self::ApiKey(key) => Ok(key.clone()),
+            Self::Jwt(jwt) => jwt.token(),
+        }
+    }
+}
+
+impl Debug for Auth {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ApiKey(_) => write!(f, "Auth::ApiKey(***)"),
+            Self::Jwt(jwt) => write!(f, "Auth::Jwt({:?})", jwt),
+        }
+    }
+}
+
+struct CachedToken {
+    token: String,
+    expires_at: SystemTime,
+}
+
+/// HS256 JWT signer with a cached token that is refreshed before it expires.
+#[derive(Clone)]
+pub struct JwtAuth {
+    id: String,
+    secret: String,
+    ttl: Duration,
+    refresh_margin: Duration,
+    cache: Arc<Mutex<Option<CachedToken>>>,
+}
+
+impl JwtAuth {
+    /// Create a signer from an `{id}.{secret}` API key.
+    pub fn new(api_key: &str) -> Result<Self, ZhipuApiError> {
+        let (id, secret) = api_key
+            .trim()
+            .split_once('.')
+            .filter(|(id, secret)| !id.is_empty() && !secret.is_empty())
+            .ok_or_else(|| {
+                ZhipuApiError::Auth("api key must have the form `{id}.{secret}`".to_string())
+            })?;
+        Ok(Self {
+            id: id.to_string(),
+            secret: secret.to_string(),
+            ttl: DEFAULT_JWT_TTL,
+            refresh_margin: DEFAULT_JWT_REFRESH_MARGIN,
+            cache: Default::default(),
+        })
+    }
+
+    /// set the lifetime of each signed token
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self.cache = Default::default();
+        self
+    }
+
+    /// set how long before expiry the cached token is replaced. It is capped at half of the ttl.
+    pub fn refresh_margin(mut self, refresh_margin: Duration) -> Self {
+        self.refresh_margin = refresh_margin;
+        self.cache = Default::default();
+        self
+    }
+
+    /// Return the cached token, signing a new one when it is missing or about to expire.
+    pub fn token(&self) -> Result<String, ZhipuApiError> {
+        let now = SystemTime::now();
+        let margin = self.refresh_margin.min(self.ttl / 2);
+        let mut cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(cached) = cache.as_ref()
+            && now + margin < cached.expires_at
+        {
+            return Ok(cached.token.clone());
+        }
+        let token = self.sign(now)?;
+        *cache = Some(CachedToken {
+            token: token.clone(),
+            expires_at: now + self.ttl,
+        });
+        Ok(token)
+    }
+
+    fn sign(&self, now: SystemTime) -> Result<String, ZhipuApiError> {
+        let timestamp = now.duration_since(UNIX_EPOCH)?.as_millis();
+        let header = json!({"alg": "HS256", "sign_type": "SIGN"});
+        let payload = json!({
+            "api_key": self.id,
+            "exp": timestamp + self.ttl.as_millis(),
+            "timestamp": timestamp,
+        });
+        let signing_input = format!(
+            "{}.{}",
+            BASE64_URL_SAFE_NO_PAD.encode(serde_json::to_vec(&header)?),
+            BASE64_URL_SAFE_NO_PAD.encode(serde_json::to_vec(&payload)?)
+        );
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.secret.as_bytes())
+            .map_err(|e| ZhipuApiError::Auth(e.to_string()))?;
+        mac.update(signing_input.as_bytes());
+        let signature = BASE64_URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes());
+        Ok(format!("{}.{}", signing_input, signature))
+    }
+}
+
+impl Debug for JwtAuth {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JwtAuth")
+            .field("id", &self.id)
+            .field("ttl", &self.ttl)
+            .field("refresh_margin", &self.refresh_margin)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    #[test]
+    fn test_jwt_rejects_key_without_secret() {
+        assert!(JwtAuth::new("only-an-id").is_err());
+        assert!(JwtAuth::new("id.").is_err());
+    }
+
+    #[test]
+    fn test_jwt_signature_and_claims() {
+        let auth = JwtAuth::new("my-id.my-secret")
+            .unwrap()
+            .ttl(Duration::from_secs(60));
+        let now = UNIX_EPOCH + Duration::from_millis(1_700_000_000_000);
+        let token = auth.sign(now).unwrap();
+        let parts: Vec<&str> = token.split('.').collect();
+        assert_eq!(parts.len(), 3);
+
+        let header: Value =
+            serde_json::from_slice(&BASE64_URL_SAFE_NO_PAD.decode(parts[0]).unwrap()).unwrap();
+        assert_eq!(header["alg"], "HS256");
+        assert_eq!(header["sign_type"], "SIGN");
+
+        let payload: Value =
+            serde_json::from_slice(&BASE64_URL_SAFE_NO_PAD.decode(parts[1]).unwrap()).unwrap();
+        assert_eq!(payload["api_key"], "my-id");
+        assert_eq!(payload["timestamp"], 1_700_000_000_000u64);
+        assert_eq!(payload["exp"], 1_700_000_060_000u64);
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"my-secret").unwrap();
+        mac.update(format!("{}.{}", parts[0], parts[1]).as_bytes());
+        mac.verify_slice(&BASE64_URL_SAFE_NO_PAD.decode(parts[2]).unwrap())
+            .unwrap();
+    }
+
+    #[test]
+    fn test_jwt_token_is_cached_until_refresh() {
+        let auth = JwtAuth::new("id.secret").unwrap();
+        let first = auth.token().unwrap();
+        assert_eq!(first, auth.token().unwrap());
+
+        let short = JwtAuth::new("id.secret").unwrap().ttl(Duration::ZERO);
+        let first = short.token().unwrap();
+        std::thread::sleep(Duration::from_millis(2));
+        assert_ne!(first, short.token().unwrap());
+    }
+}
@@ -0,0 +1,504 @@
+//! # Reusable API client
+//!
+//! [`ZhipuClient`] keeps one HTTP connection pool, the API key, the base URL and the default
+//! headers for every call. It is cheap to clone (the state lives behind an [`Arc`]), so a single
+//! instance can be shared across tasks.
+//!
+//! The authentication strategy is configurable through [`ZhipuClientBuilder::auth`], for example
+//! [`Auth::jwt`] to send signed short-lived tokens instead of the raw key. Failed requests are
+//! retried according to the [`RetryPolicy`], and an optional [`RateLimiter`] keeps the calls of
+//! each model within its RPM, TPM and concurrency limits. Every HTTP call goes through the
+//! [`Middleware`] chain of the client. With the `tracing` feature each call gets its own span.
+//!
+//! Every non-streaming API is an [`Endpoint`] and goes through [`ZhipuClient::execute`]; the
+//! typed methods such as [`ZhipuClient::chat`] are thin wrappers around it.
+
+use crate::{
+    api_resource::{
+        chat::{
+            self, ChatApiRequest, ChatApiResponse, ChatApiResponseStream, ChatStreamEvent,
+            response_chunk_stream, response_context_stream,
+        },
+        images::{ImagesApiRequest, ImagesChatApiResponse},
+        rtav::{SessionSink, SessionStream, connect_realtime_session},
+        tokenizer::{TokenizerApiRequest, TokenizerApiResponse},
+        videos::{
+            VideosApiAsynRequest, VideosApiRequest, VideosChatApiAsynResponse,
+            VideosChatApiResponse,
+        },
+    },
+    auth::Auth,
+    endpoint::{Endpoint, EndpointConfig, decode_response},
+    error::ZhipuApiError,
+    http::{api_request, get_request, post_request},
+    limit::{RateLimiter, RatePermit},
+    middleware::{Middleware, Next},
+    retry::{RetryPolicy, generate_request_id},
+    telemetry::CallSpan,
+};
+use async_stream::stream;
+use bytes::Bytes;
+use futures::{Stream, StreamExt};
+use log::warn;
+use reqwest::{
+    Response,
+    header::{HeaderMap, HeaderName, HeaderValue},
+};
+use std::{pin::pin, sync::Arc, time::Duration};
+use tokio::time::sleep;
+
+struct ClientInner {
+    http: reqwest::Client,
+    auth: Auth,
+    endpoint: EndpointConfig,
+    retry: RetryPolicy,
+    limiter: Option<Arc<RateLimiter>>,
+    middlewares: Vec<Arc<dyn Middleware>>,
+}
+
+/// A cloneable client holding a pooled HTTP connection.
+/// ```ignore
+/// let client = ZhipuClient::new(&api_key);
+/// let (_, request) = BigModel::<Chat>::new("glm-4-flash")
+///     .add_message(chat_simple_message!(Role::User, "hello"))
+///     .build();
+/// let response = client.chat(&request).await?;
+/// ```
+#[derive(Clone)]
+pub struct ZhipuClient {
+    inner: Arc<ClientInner>,
+}
+
+impl ZhipuClient {
+    /// Create a client with the default configuration.
+    pub fn new(api_key: &str) -> Self {
+        Self::builder(api_key)
+            .build()
+            .expect("default http client must be constructible")
+    }
+
+    /// Start configuring a client, see [`ZhipuClientBuilder`].
+    pub fn builder(api_key: &str) -> ZhipuClientBuilder {
+        ZhipuClientBuilder::new(api_key)
+    }
+
+    /// The authentication strategy used by this client.
+    pub fn auth(&self) -> &Auth {
+        &self.inner.auth
+    }
+
+    /// The endpoint every request of this client is sent to.
+    pub fn endpoint(&self) -> &EndpointConfig {
+        &self.inner.endpoint
+    }
+
+    /// The retry policy of this client.
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.inner.retry
+    }
+
+    /// The rate limiter of this client, if one was configured.
+    pub fn rate_limiter(&self) -> Option<&RateLimiter> {
+        self.inner.limiter.as_deref()
+    }
+
+    /// The base URL every endpoint path is appended to.
+    pub fn base_url(&self) -> &str {
+        self.inner.endpoint.base_url()
+    }
+
+    /// The underlying pooled `reqwest` client.
+    pub fn http_client(&self) -> &reqwest::Client {
+        &self.inner.http
+    }
+
+    /// Join an endpoint path such as `/chat/completions` onto the base URL.
+    pub fn url(&self, path: &str) -> String {
+        self.inner.endpoint.url(path)
+    }
+
+    /// Send a JSON body with `POST` to `api_url`, retrying according to the [`RetryPolicy`].
+    pub async fn post(
+        &self,
+        api_url: &str,
+        request_json: String,
+    ) -> Result<Response, ZhipuApiError> {
+        let body = Bytes::from(request_json);
+        self.send_with_retry(|http, token| post_request(http, api_url, token, body.clone()))
+            .await
+    }
+
+    /// Send a `GET` to `api_url`, retrying according to the [`RetryPolicy`].
+    pub async fn get(&self, api_url: &str) -> Result<Response, ZhipuApiError> {
+        self.send_with_retry(|http, token| get_request(http, api_url, token))
+            .await
+    }
+
+    /// Wait for the [`RateLimiter`] of `model`. The permit covers every retry of the call.
+    async fn acquire(&self, model: &str, tokens: u32) -> Option<RatePermit> {
+        match &self.inner.limiter {
+            Some(limiter) => Some(limiter.acquire(model, tokens).await),
+            None => None,
+        }
+    }
+
+    async fn send_with_retry<F>(&self, make_request: F) -> Result<Response, ZhipuApiError>
+    where
+        F: Fn(&reqwest::Client, &str) -> reqwest::RequestBuilder,
+    {
+        let policy = &self.inner.retry;
+        let mut attempt = 1;
+        loop {
+            // the token is fetched per attempt, so a long backoff cannot outlive a JWT
+            let token = self.inner.auth.bearer_token()?;
+            let request = make_request(&self.inner.http, &token).build()?;
+            let result = Next::new(&self.inner.http, &self.inner.middlewares)
+                .run(request)
+                .await;
+            let last = attempt >= policy.get_max_attempts();
+            let delay = match result {
+                Ok(response) if !last && policy.is_retryable_status(response.status()) => {
+                    let delay = policy.delay_for(attempt, &response);
+                    warn!(
+                        "{} responded {}, retrying in {:?} (attempt {}/{})",
+                        response.url(),
+                        response.status(),
+                        delay,
+                        attempt,
+                        policy.get_max_attempts()
+                    );
+                    delay
+                }
+                Err(ZhipuApiError::Reqwest(e)) if !last && policy.is_retryable_error(&e) => {
+                    let delay = policy.backoff(attempt);
+                    warn!(
+                        "request failed: {}, retrying in {:?} (attempt {}/{})",
+                        e,
+                        delay,
+                        attempt,
+                        policy.get_max_attempts()
+                    );
+                    delay
+                }
+                result => return result,
+            };
+            sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Send any [`Endpoint`] with the authentication, retries, rate limiter, middleware and
+    /// tracing of this client, and decode its typed response.
+    /// ```ignore
+    /// let (_, request) = BigModel::<Images>::new("cogview-3-flash").prompt("a cat").build();
+    /// let response = client.execute(&request).await?;
+    /// ```
+    pub async fn execute<E: Endpoint>(&self, endpoint: &E) -> Result<E::Response, ZhipuApiError> {
+        let url = self.url(&endpoint.path());
+        let model = endpoint.model();
+        let span = CallSpan::new(
+            E::NAME,
+            model.unwrap_or_default(),
+            &url,
+            endpoint.request_id(),
+        );
+        traced(&span, async {
+            let body = endpoint.body()?;
+            let _permit = match model {
+                Some(model) => {
+                    let tokens = body.as_deref().map_or(0, |b| endpoint.estimate_tokens(b));
+                    self.acquire(model, tokens).await
+                }
+                None => None,
+            };
+            let body = body.map(Bytes::from);
+            let method = endpoint.method();
+            let response = self
+                .send_with_retry(|http, token| {
+                    api_request(http, method.clone(), &url, token, body.clone())
+                })
+                .await?;
+            span.record_status(response.status());
+            let response = decode_response::<E::Response>(response).await?;
+            if let Some(usage) = E::usage(&response) {
+                span.record_usage(usage);
+            }
+            Ok(response)
+        })
+        .await
+    }
+
+    /// Call the chat completion API and decode the whole response.
+    /// A `request_id` is generated when the request has none, so retries can be de-duplicated.
+    pub async fn chat(&self, request: &ChatApiRequest) -> Result<ChatApiResponse, ZhipuApiError> {
+        self.execute(&with_request_id(request)).await
+    }
+
+    /// Call the chat completion API with a streaming request (`stream_enable(true)`).
+    pub async fn chat_stream(
+        &self,
+        request: &ChatApiRequest,
+    ) -> Result<impl Stream<Item = Result<ChatStreamEvent, ZhipuApiError>>, ZhipuApiError> {
+        let (permit, response, mut span) = self.start_chat_stream("chat_stream", request).await?;
+        Ok(stream! {
+            // the concurrency slot is released once the stream is dropped
+            let _permit = permit;
+            let mut events = pin!(response_context_stream(response));
+            let mut failed = false;
+            while let Some(event) = span.instrument(events.next()).await {
+                match &event {
+                    Ok(ChatStreamEvent::Usage(usage)) => span.record_usage(usage),
+                    Ok(event) if event.is_delta() => span.record_first_token(),
+                    Ok(_) => {}
+                    Err(e) => {
+                        failed = true;
+                        span.finish(Some(e));
+                    }
+                }
+                yield event;
+            }
+            if !failed {
+                span.finish(None);
+            }
+        })
+    }
+
+    /// [`Self::chat_stream`] yielding the raw chunks, for a
+    /// [`ChatResponseAccumulator`](chat::ChatResponseAccumulator) to keep their id, creation
+    /// time and model.
+    pub async fn chat_stream_chunks(
+        &self,
+        request: &ChatApiRequest,
+    ) -> Result<impl Stream<Item = Result<ChatApiResponseStream, ZhipuApiError>>, ZhipuApiError>
+    {
+        let (permit, response, mut span) = self
+            .start_chat_stream("chat_stream_chunks", request)
+            .await?;
+        Ok(stream! {
+            let _permit = permit;
+            let mut chunks = pin!(response_chunk_stream(response));
+            let mut failed = false;
+            while let Some(chunk) = span.instrument(chunks.next()).await {
+                match &chunk {
+                    Ok(chunk) => {
+                        if let Some(usage) = chunk.get_usage() {
+                            span.record_usage(usage);
+                        }
+                        if chunk.events().iter().any(ChatStreamEvent::is_delta) {
+                            span.record_first_token();
+                        }
+                    }
+                    Err(e) => {
+                        failed = true;
+                        span.finish(Some(e));
+                    }
+                }
+                yield chunk;
+            }
+            if !failed {
+                span.finish(None);
+            }
+        })
+    }
+
+    /// Send a streaming request, waiting for the rate limiter, up to the response headers.
+    async fn start_chat_stream(
+        &self,
+        api: &'static str,
+        request: &ChatApiRequest,
+    ) -> Result<(Option<RatePermit>, Response, CallSpan), ZhipuApiError> {
+        let request = with_request_id(request);
+        let url = self.url(chat::api::API_PATH);
+        let span = CallSpan::new(api, request.model(), &url, request.request_id());
+        let started = span
+            .instrument(async {
+                let body = request.to_json()?;
+                let permit = self
+                    .acquire(request.model(), request.estimate_tokens(&body))
+                    .await;
+                let response = self.post(&url, body).await?;
+                span.record_status(response.status());
+                Ok((permit, response))
+            })
+            .await;
+        match started {
+            Ok((permit, response)) => Ok((permit, response, span)),
+            Err(e) => {
+                span.finish(Some(&e));
+                Err(e)
+            }
+        }
+    }
+
+    /// Call the image generation API.
+    pub async fn images(
+        &self,
+        request: &ImagesApiRequest,
+    ) -> Result<ImagesChatApiResponse, ZhipuApiError> {
+        self.execute(request).await
+    }
+
+    /// Submit a video generation task.
+    pub async fn videos(
+        &self,
+        request: &VideosApiRequest,
+    ) -> Result<VideosChatApiResponse, ZhipuApiError> {
+        let mut request = request.clone();
+        request.ensure_request_id(generate_request_id);
+        self.execute(&request).await
+    }
+
+    /// Query the result of a video generation task by the id returned from [`Self::videos`].
+    pub async fn videos_result(
+        &self,
+        task_id: &str,
+    ) -> Result<VideosChatApiAsynResponse, ZhipuApiError> {
+        self.execute(&VideosApiAsynRequest::new(task_id)).await
+    }
+
+    /// Count the tokens of a prompt with the tokenizer of its model.
+    /// ```ignore
+    /// let (_, request) = builder.build();
+    /// let count = client.tokenize(&TokenizerApiRequest::from(&request)).await?;
+    /// ```
+    pub async fn tokenize(
+        &self,
+        request: &TokenizerApiRequest,
+    ) -> Result<TokenizerApiResponse, ZhipuApiError> {
+        self.execute(request).await
+    }
+
+    /// Open a GLM-Realtime session on the realtime URL of the endpoint.
+    pub async fn realtime(&self) -> Result<(SessionSink, SessionStream), ZhipuApiError> {
+        connect_realtime_session(
+            self.inner.endpoint.realtime_url(),
+            &self.inner.auth.bearer_token()?,
+        )
+        .await
+    }
+}
+
+/// Run one API call inside `span` and record its outcome.
+async fn traced<T>(
+    span: &CallSpan,
+    call: impl Future<Output = Result<T, ZhipuApiError>>,
+) -> Result<T, ZhipuApiError> {
+    let result = span.instrument(call).await;
+    span.finish(result.as_ref().err());
+    result
+}
+
+fn with_request_id(request: &ChatApiRequest) -> ChatApiRequest {
+    let mut request = request.clone();
+    request.ensure_request_id(generate_request_id);
+    request
+}
+
+/// Builder of [`ZhipuClient`].
+pub struct ZhipuClientBuilder {
+    auth: Auth,
+    endpoint: Option<EndpointConfig>,
+    headers: Vec<(String, String)>,
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    retry: RetryPolicy,
+    limiter: Option<RateLimiter>,
+    middlewares: Vec<Arc<dyn Middleware>>,
+}
+
+impl ZhipuClientBuilder {
+    pub fn new(api_key: &str) -> Self {
+        Self {
+            auth: Auth::api_key(api_key),
+            endpoint: None,
+            headers: Vec::new(),
+            timeout: None,
+            connect_timeout: None,
+            retry: Default::default(),
+            limiter: None,
+            middlewares: Vec::new(),
+        }
+    }
+
+    /// set the authentication strategy, default is [`Auth::ApiKey`] with the key given to `new`
+    pub fn auth(mut self, auth: Auth) -> Self {
+        self.auth = auth;
+        self
+    }
+
+    /// set the endpoint, default is [`EndpointConfig::global`] at the time of `build`
+    pub fn endpoint(mut self, endpoint: EndpointConfig) -> Self {
+        self.endpoint = Some(endpoint);
+        self
+    }
+
+    /// set a custom base url, see [`EndpointConfig::custom`]
+    pub fn base_url(mut self, base_url: &str) -> Self {
+        self.endpoint = Some(EndpointConfig::custom(base_url));
+        self
+    }
+
+    /// add a header sent with every request
+    pub fn default_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// set the total timeout of a request
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// set the timeout of establishing a connection
+    pub fn connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = Some(connect_timeout);
+        self
+    }
+
+    /// set the retry policy, default is [`RetryPolicy::new`]. Use [`RetryPolicy::none`] to disable it.
+    pub fn retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// set per-model RPM, TPM and concurrency limits, default is no limit
+    pub fn rate_limiter(mut self, limiter: RateLimiter) -> Self {
+        self.limiter = Some(limiter);
+        self
+    }
+
+    /// add a middleware around every HTTP call, see [`Middleware`]. The first one added is the
+    /// outermost.
+    pub fn middleware(mut self, middleware: impl Middleware) -> Self {
+        self.middlewares.push(Arc::new(middleware));
+        self
+    }
+
+    pub fn build(self) -> Result<ZhipuClient, ZhipuApiError> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            headers.insert(
+                HeaderName::from_bytes(name.as_bytes())?,
+                HeaderValue::from_str(value)?,
+            );
+        }
+        let mut http = reqwest::Client::builder().default_headers(headers);
+        if let Some(timeout) = self.timeout {
+            http = http.timeout(timeout);
+        }
+        if let Some(connect_timeout) = self.connect_timeout {
+            http = http.connect_timeout(connect_timeout);
+        }
+        Ok(ZhipuClient {
+            inner: Arc::new(ClientInner {
+                http: http.build()?,
+                auth: self.auth,
+                endpoint: self.endpoint.unwrap_or_else(EndpointConfig::global),
+                retry: self.retry,
+                limiter: self.limiter.map(Arc::new),
+                middlewares: self.middlewares,
+            }),
+        })
+    }
+}
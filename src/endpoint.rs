@@ -0,0 +1,332 @@
+//! # API endpoint configuration
+//!
+//! Every request builder and the realtime session resolve their URL through an
+//! [`EndpointConfig`]. Builders use [`EndpointConfig::global`] unless one is set explicitly, so
+//! switching the whole process to another host only takes one [`EndpointConfig::set_global`].
+//!
+//! Each HTTP API is described by an [`Endpoint`]: its method, path, body and typed response.
+//! [`ZhipuClient::execute`](crate::prelude::ZhipuClient::execute) sends any of them through the
+//! same authentication, retry, rate limit and middleware path and decodes the response.
+
+use crate::{api_resource::chat::Usage, error::ZhipuApiError};
+use log::trace;
+use reqwest::{Method, Response};
+use serde::de::DeserializeOwned;
+use std::{
+    convert::Infallible,
+    fmt,
+    str::FromStr,
+    sync::{OnceLock, RwLock},
+};
+
+/// The default base URL of the BigModel open platform (mainland China).
+pub const DEFAULT_BASE_URL: &str = "https://open.bigmodel.cn/api/paas/v4";
+/// The base URL of the international platform.
+pub const INTERNATIONAL_BASE_URL: &str = "https://api.z.ai/api/paas/v4";
+/// The base URL of the GLM coding plan.
+pub const CODING_PLAN_BASE_URL: &str = "https://open.bigmodel.cn/api/coding/paas/v4";
+
+/// Named endpoint profiles.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EndpointProfile {
+    /// open.bigmodel.cn
+    Cn,
+    /// api.z.ai
+    International,
+    /// the coding plan endpoint on open.bigmodel.cn
+    CodingPlan,
+    /// any other base URL, e.g. a corporate proxy or a local stand-in server
+    Custom(String),
+}
+
+impl fmt::Display for EndpointProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Cn => write!(f, "cn"),
+            Self::International => write!(f, "international"),
+            Self::CodingPlan => write!(f, "coding_plan"),
+            Self::Custom(url) => write!(f, "{}", url),
+        }
+    }
+}
+
+impl FromStr for EndpointProfile {
+    type Err = Infallible;
+
+    /// `cn`, `international` (or `intl`), `coding_plan` (or `coding`); anything else is taken as a
+    /// custom base URL.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.trim().to_ascii_lowercase().as_str() {
+            "cn" | "china" => Self::Cn,
+            "international" | "intl" | "global" => Self::International,
+            "coding_plan" | "coding-plan" | "coding" => Self::CodingPlan,
+            _ => Self::Custom(s.trim().to_string()),
+        })
+    }
+}
+
+/// Where the HTTP APIs and the realtime WebSocket live.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EndpointConfig {
+    base_url: String,
+    realtime_url: String,
+}
+
+impl Default for EndpointConfig {
+    fn default() -> Self {
+        Self::cn()
+    }
+}
+
+impl From<EndpointProfile> for EndpointConfig {
+    fn from(profile: EndpointProfile) -> Self {
+        match profile {
+            EndpointProfile::Cn => Self::custom(DEFAULT_BASE_URL),
+            EndpointProfile::International => Self::custom(INTERNATIONAL_BASE_URL),
+            EndpointProfile::CodingPlan => Self::custom(CODING_PLAN_BASE_URL)
+                .with_realtime_url(&realtime_url_of(DEFAULT_BASE_URL)),
+            EndpointProfile::Custom(base_url) => Self::custom(&base_url),
+        }
+    }
+}
+
+impl EndpointConfig {
+    /// open.bigmodel.cn, the default.
+    pub fn cn() -> Self {
+        EndpointProfile::Cn.into()
+    }
+
+    /// api.z.ai
+    pub fn international() -> Self {
+        EndpointProfile::International.into()
+    }
+
+    /// The coding plan endpoint. The realtime API is not part of the plan and stays on the CN host.
+    pub fn coding_plan() -> Self {
+        EndpointProfile::CodingPlan.into()
+    }
+
+    /// Any base URL such as `http://127.0.0.1:8080/api/paas/v4`. The realtime URL is derived by
+    /// switching the scheme to `ws`/`wss` and appending `/realtime`.
+    pub fn custom(base_url: &str) -> Self {
+        let base_url = base_url.trim_end_matches('/').to_string();
+        Self {
+            realtime_url: realtime_url_of(&base_url),
+            base_url,
+        }
+    }
+
+    /// override the realtime WebSocket URL
+    pub fn with_realtime_url(mut self, realtime_url: &str) -> Self {
+        self.realtime_url = realtime_url.to_string();
+        self
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    pub fn realtime_url(&self) -> &str {
+        &self.realtime_url
+    }
+
+    /// Join an endpoint path such as `/chat/completions` onto the base URL.
+    pub fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.base_url, path.trim_start_matches('/'))
+    }
+
+    /// The process-wide configuration used by builders that were not given one.
+    pub fn global() -> Self {
+        global_config()
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Replace the process-wide configuration returned by [`Self::global`].
+    pub fn set_global(config: EndpointConfig) {
+        *global_config().write().unwrap_or_else(|e| e.into_inner()) = config;
+    }
+}
+
+/// One HTTP API of the platform, sent with
+/// [`ZhipuClient::execute`](crate::prelude::ZhipuClient::execute).
+/// ```ignore
+/// #[derive(Serialize)]
+/// struct Embeddings {
+///     model: String,
+///     input: Vec<String>,
+/// }
+///
+/// impl Endpoint for Embeddings {
+///     type Response = EmbeddingsResponse;
+///     const NAME: &'static str = "embeddings";
+///
+///     fn path(&self) -> String {
+///         "/embeddings".to_string()
+///     }
+///
+///     fn body(&self) -> Result<Option<String>, ZhipuApiError> {
+///         Ok(Some(serde_json::to_string(self)?))
+///     }
+///
+///     fn model(&self) -> Option<&str> {
+///         Some(&self.model)
+///     }
+/// }
+///
+/// let response = client.execute(&embeddings).await?;
+/// ```
+pub trait Endpoint {
+    /// the decoded body of a successful response
+    type Response: DeserializeOwned;
+
+    /// the name of the API in tracing spans, e.g. `"chat"`
+    const NAME: &'static str;
+
+    /// the HTTP method, `POST` unless overridden
+    fn method(&self) -> Method {
+        Method::POST
+    }
+
+    /// the path joined onto the base URL of the client, e.g. `/chat/completions`
+    fn path(&self) -> String;
+
+    /// the JSON body, or `None` to send the request without one
+    fn body(&self) -> Result<Option<String>, ZhipuApiError>;
+
+    /// the model the call is charged to, used by the rate limiter
+    fn model(&self) -> Option<&str> {
+        None
+    }
+
+    /// the request id, recorded on the tracing span
+    fn request_id(&self) -> Option<&str> {
+        None
+    }
+
+    /// the tokens the call is expected to consume, for the TPM limit of the rate limiter
+    fn estimate_tokens(&self, _body: &str) -> u32 {
+        0
+    }
+
+    /// the token usage reported by the response, recorded on the tracing span
+    fn usage(_response: &Self::Response) -> Option<&Usage> {
+        None
+    }
+}
+
+/// Decode the JSON body of a successful response, or turn a failed one into
+/// [`ZhipuApiError::Api`].
+pub(crate) async fn decode_response<T: DeserializeOwned>(
+    response: Response,
+) -> Result<T, ZhipuApiError> {
+    if response.status().is_success() {
+        let text = response.text().await?;
+        trace!("{}", text);
+        Ok(serde_json::from_str(&text)?)
+    } else {
+        Err(ZhipuApiError::from_response(response).await)
+    }
+}
+
+fn global_config() -> &'static RwLock<EndpointConfig> {
+    static GLOBAL: OnceLock<RwLock<EndpointConfig>> = OnceLock::new();
+    GLOBAL.get_or_init(Default::default)
+}
+
+fn realtime_url_of(base_url: &str) -> String {
+    let base_url = if let Some(rest) = base_url.strip_prefix("https://") {
+        format!("wss://{}", rest)
+    } else if let Some(rest) = base_url.strip_prefix("http://") {
+        format!("ws://{}", rest)
+    } else {
+        base_url.to_string()
+    };
+    format!("{}/realtime", base_url.trim_end_matches('/'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_custom_endpoint_derives_realtime_url() {
+        let endpoint = EndpointConfig::custom("http://127.0.0.1:8080/api/paas/v4/");
+        assert_eq!(
+            endpoint.url("/chat/completions"),
+            "http://127.0.0.1:8080/api/paas/v4/chat/completions"
+        );
+        assert_eq!(
+            endpoint.realtime_url(),
+            "ws://127.0.0.1:8080/api/paas/v4/realtime"
+        );
+        assert_eq!(
+            EndpointConfig::cn().realtime_url(),
+            "wss://open.bigmodel.cn/api/paas/v4/realtime"
+        );
+    }
+
+    #[test]
+    fn test_profile_from_str() {
+        assert_eq!("CN".parse(), Ok(EndpointProfile::Cn));
+        assert_eq!("intl".parse(), Ok(EndpointProfile::International));
+        assert_eq!(
+            "https://proxy.local/v4".parse(),
+            Ok(EndpointProfile::Custom(
+                "https://proxy.local/v4".to_string()
+            ))
+        );
+        assert_eq!(
+            EndpointConfig::from(EndpointProfile::International).base_url(),
+            INTERNATIONAL_BASE_URL
+        );
+    }
+
+    #[tokio::test]
+    async fn test_execute_custom_endpoint() {
+        use crate::testing::{Mock, MockResponse, MockServer};
+
+        #[derive(serde::Deserialize)]
+        struct Balance {
+            total: u32,
+        }
+
+        struct GetBalance(&'static str);
+
+        impl Endpoint for GetBalance {
+            type Response = Balance;
+            const NAME: &'static str = "balance";
+
+            fn method(&self) -> Method {
+                Method::GET
+            }
+
+            fn path(&self) -> String {
+                format!("/balance/{}", self.0)
+            }
+
+            fn body(&self) -> Result<Option<String>, ZhipuApiError> {
+                Ok(None)
+            }
+        }
+
+        let server = MockServer::start().await.unwrap();
+        server
+            .mock(Mock::get(
+                "/balance/acct",
+                MockResponse::json(&serde_json::json!({ "total": 42 })),
+            ))
+            .mock(Mock::get(
+                "/balance/none",
+                MockResponse::error(404, "1211", "no such account"),
+            ));
+        let client = server.client();
+
+        let balance = client.execute(&GetBalance("acct")).await.unwrap();
+        assert_eq!(balance.total, 42);
+        let error = client.execute(&GetBalance("none")).await.err().unwrap();
+        assert_eq!(error.status(), Some(404));
+        assert_eq!(server.received()[0].method(), "GET");
+    }
+}
@@ -1,6 +1,10 @@
 use crate::api_resource::rtav::Error as RealtimeError;
 use base64::DecodeError;
-use reqwest::{Error as ReqwestError, header::InvalidHeaderValue};
+use reqwest::{
+    Error as ReqwestError, Response,
+    header::{InvalidHeaderName, InvalidHeaderValue},
+};
+use serde::Deserialize;
 use serde_json::Error as JsonError;
 use std::{
     error::Error,
@@ -13,35 +17,278 @@ use tokio_tungstenite::tungstenite::Error as TungsteniteError;
 /// 通用的API错误类型
 #[derive(Debug)]
 pub enum ZhipuApiError {
+    /// The platform answered with a non-success status. `code` and `message` come from the
+    /// `{"error":{"code":"1301","message":"..."}}` body when it could be parsed.
+    Api {
+        status: u16,
+        code: Option<String>,
+        message: String,
+        request_id: Option<String>,
+    },
+    Auth(String),
     Decode(DecodeError),
     InvalidHeader(InvalidHeaderValue),
+    InvalidHeaderName(InvalidHeaderName),
+    /// The request was rejected before sending, e.g. it asks for a feature the model lacks.
+    InvalidRequest(String),
     Io(IoError),
     Json(JsonError),
     Realtime(RealtimeError),
     Reqwest(ReqwestError),
-    StatusCode(String),
     SystemTime(SystemTimeError),
     TungsteniteError(TungsteniteError),
 }
 
+/// Classification of a [`ZhipuApiError`], for alerting and retry decisions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    /// missing, invalid or expired credentials, or no permission for the API
+    Auth,
+    /// too many requests or too much concurrency, worth retrying later
+    RateLimited,
+    /// the balance, the daily limit or the plan is exhausted
+    Quota,
+    /// the input or the output hit the content filter
+    ContentFiltered,
+    /// the request itself is wrong: bad parameters, unknown model, prompt too long...
+    InvalidRequest,
+    /// the platform failed to process a valid request
+    Server,
+    /// the connection failed, timed out or was interrupted
+    Transport,
+    /// a response could not be decoded
+    Decode,
+    /// anything else
+    Other,
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let kind = match self {
+            Self::Auth => "auth",
+            Self::RateLimited => "rate_limited",
+            Self::Quota => "quota",
+            Self::ContentFiltered => "content_filtered",
+            Self::InvalidRequest => "invalid_request",
+            Self::Server => "server",
+            Self::Transport => "transport",
+            Self::Decode => "decode",
+            Self::Other => "other",
+        };
+        write!(f, "{}", kind)
+    }
+}
+
+impl ErrorKind {
+    /// Classify a platform error code, see <https://docs.bigmodel.cn/cn/faq/api-code>
+    pub fn from_code(code: &str) -> Option<Self> {
+        let code: u32 = code.trim().parse().ok()?;
+        Some(match code {
+            1000..=1004 | 1100 | 1110..=1112 | 1120 | 1220 => Self::Auth,
+            1113 | 1121 | 1304 | 1308 | 1309 => Self::Quota,
+            1301 => Self::ContentFiltered,
+            1302 | 1303 | 1305 => Self::RateLimited,
+            1200..=1219 | 1221 | 1222 | 1260..=1269 | 1300 => Self::InvalidRequest,
+            1230..=1239 | 500..=599 => Self::Server,
+            _ => return None,
+        })
+    }
+
+    /// Classify an HTTP status code.
+    pub fn from_status(status: u16) -> Self {
+        match status {
+            401 | 403 => Self::Auth,
+            408 | 504 => Self::Transport,
+            429 => Self::RateLimited,
+            400..=499 => Self::InvalidRequest,
+            500..=599 => Self::Server,
+            _ => Self::Other,
+        }
+    }
+
+    /// Whether sending the same request again may succeed.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::RateLimited | Self::Server | Self::Transport)
+    }
+}
+
+#[derive(Deserialize)]
+struct ApiErrorBody {
+    error: Option<ApiErrorDetail>,
+    request_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ApiErrorDetail {
+    #[serde(default, deserialize_with = "deserialize_code")]
+    code: Option<String>,
+    message: Option<String>,
+}
+
+/// the platform sends `code` as a string, but be lenient about numbers
+fn deserialize_code<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(
+        match Option::<serde_json::Value>::deserialize(deserializer)? {
+            Some(serde_json::Value::String(code)) => Some(code),
+            Some(serde_json::Value::Number(code)) => Some(code.to_string()),
+            _ => None,
+        },
+    )
+}
+
+impl ZhipuApiError {
+    /// Build an [`ZhipuApiError::Api`] from a status code and the raw response body.
+    pub fn from_body(status: u16, body: &str, request_id: Option<String>) -> Self {
+        let parsed = serde_json::from_str::<ApiErrorBody>(body).ok();
+        let request_id = parsed
+            .as_ref()
+            .and_then(|b| b.request_id.clone())
+            .or(request_id);
+        let (code, message) = match parsed.and_then(|b| b.error) {
+            Some(detail) => (detail.code, detail.message),
+            None => (None, None),
+        };
+        let message = message.unwrap_or_else(|| {
+            if body.trim().is_empty() {
+                format!("Failed to fetch data: {}", status)
+            } else {
+                body.trim().to_string()
+            }
+        });
+        Self::Api {
+            status,
+            code,
+            message,
+            request_id,
+        }
+    }
+
+    /// Consume a non-success response and turn its body into an [`ZhipuApiError::Api`].
+    pub async fn from_response(response: Response) -> Self {
+        let status = response.status().as_u16();
+        let request_id = ["x-request-id", "x-log-id"]
+            .iter()
+            .find_map(|name| response.headers().get(*name))
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string());
+        match response.text().await {
+            Ok(body) => Self::from_body(status, &body, request_id),
+            Err(e) => e.into(),
+        }
+    }
+
+    /// Classify the error.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Api { status, code, .. } => code
+                .as_deref()
+                .and_then(ErrorKind::from_code)
+                .unwrap_or_else(|| ErrorKind::from_status(*status)),
+            Self::Auth(_) => ErrorKind::Auth,
+            Self::Decode(_) | Self::Json(_) => ErrorKind::Decode,
+            Self::InvalidHeader(_) | Self::InvalidHeaderName(_) | Self::InvalidRequest(_) => {
+                ErrorKind::InvalidRequest
+            }
+            Self::Io(_) | Self::TungsteniteError(_) => ErrorKind::Transport,
+            Self::Realtime(e) => {
+                ErrorKind::from_code(&e.code).unwrap_or(match e.r#type.as_deref() {
+                    Some("invalid_request_error") => ErrorKind::InvalidRequest,
+                    Some("server_error") => ErrorKind::Server,
+                    _ => ErrorKind::Other,
+                })
+            }
+            Self::Reqwest(e) => {
+                if e.is_decode() {
+                    ErrorKind::Decode
+                } else if e.is_builder() {
+                    ErrorKind::InvalidRequest
+                } else if let Some(status) = e.status() {
+                    ErrorKind::from_status(status.as_u16())
+                } else {
+                    ErrorKind::Transport
+                }
+            }
+            Self::SystemTime(_) => ErrorKind::Other,
+        }
+    }
+
+    /// Whether sending the same request again may succeed, see [`ErrorKind::is_retryable`].
+    pub fn is_retryable(&self) -> bool {
+        self.kind().is_retryable()
+    }
+
+    /// The HTTP status of an [`ZhipuApiError::Api`] error.
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            Self::Api { status, .. } => Some(*status),
+            Self::Reqwest(e) => e.status().map(|s| s.as_u16()),
+            _ => None,
+        }
+    }
+
+    /// The platform error code, e.g. `1301`.
+    pub fn code(&self) -> Option<&str> {
+        match self {
+            Self::Api { code, .. } => code.as_deref(),
+            Self::Realtime(e) => Some(&e.code),
+            _ => None,
+        }
+    }
+}
+
 impl Display for ZhipuApiError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "ZhipuApiError: ")?;
         match self {
+            Self::Api {
+                status,
+                code,
+                message,
+                request_id,
+            } => {
+                write!(f, "{} (status: {}", message, status)?;
+                if let Some(code) = code {
+                    write!(f, ", code: {}", code)?;
+                }
+                if let Some(request_id) = request_id {
+                    write!(f, ", request_id: {}", request_id)?;
+                }
+                write!(f, ")")
+            }
+            Self::Auth(e) => Display::fmt(e, f),
             Self::Decode(e) => Display::fmt(e, f),
             Self::InvalidHeader(e) => Display::fmt(e, f),
+            Self::InvalidHeaderName(e) => Display::fmt(e, f),
+            Self::InvalidRequest(e) => Display::fmt(e, f),
             Self::Io(e) => Display::fmt(e, f),
             Self::Json(e) => Display::fmt(e, f),
             Self::Realtime(e) => Display::fmt(e, f),
             Self::Reqwest(e) => Display::fmt(e, f),
-            Self::StatusCode(e) => Display::fmt(e, f),
             Self::SystemTime(e) => Display::fmt(e, f),
             Self::TungsteniteError(e) => Display::fmt(e, f),
         }
     }
 }
 
-impl Error for ZhipuApiError {}
+impl Error for ZhipuApiError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Api { .. } | Self::Auth(_) | Self::InvalidRequest(_) => None,
+            Self::Decode(e) => Some(e),
+            Self::InvalidHeader(e) => Some(e),
+            Self::InvalidHeaderName(e) => Some(e),
+            Self::Io(e) => Some(e),
+            Self::Json(e) => Some(e),
+            Self::Realtime(e) => Some(e),
+            Self::Reqwest(e) => Some(e),
+            Self::SystemTime(e) => Some(e),
+            Self::TungsteniteError(e) => Some(e),
+        }
+    }
+}
 
 impl From<IoError> for ZhipuApiError {
     fn from(value: IoError) -> Self {
@@ -73,6 +320,12 @@ impl From<InvalidHeaderValue> for ZhipuApiError {
     }
 }
 
+impl From<InvalidHeaderName> for ZhipuApiError {
+    fn from(value: InvalidHeaderName) -> Self {
+        Self::InvalidHeaderName(value)
+    }
+}
+
 impl From<DecodeError> for ZhipuApiError {
     fn from(value: DecodeError) -> Self {
         Self::Decode(value)
@@ -90,3 +343,38 @@ impl From<ReqwestError> for ZhipuApiError {
         Self::Reqwest(value)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_api_error_body_is_parsed() {
+        let error = ZhipuApiError::from_body(
+            400,
+            r#"{"error":{"code":"1301","message":"系统检测到输入或生成内容可能包含不安全或敏感内容"}}"#,
+            Some("req-1".to_string()),
+        );
+        assert_eq!(error.status(), Some(400));
+        assert_eq!(error.code(), Some("1301"));
+        assert_eq!(error.kind(), ErrorKind::ContentFiltered);
+        assert!(!error.is_retryable());
+        assert!(error.to_string().contains("request_id: req-1"));
+    }
+
+    #[test]
+    fn test_api_error_falls_back_to_status() {
+        let error = ZhipuApiError::from_body(429, "", None);
+        assert_eq!(error.kind(), ErrorKind::RateLimited);
+        assert!(error.is_retryable());
+
+        let error = ZhipuApiError::from_body(503, "<html>busy</html>", None);
+        assert_eq!(error.kind(), ErrorKind::Server);
+        assert_eq!(error.code(), None);
+
+        let error =
+            ZhipuApiError::from_body(429, r#"{"error":{"code":1113,"message":"余额不足"}}"#, None);
+        assert_eq!(error.kind(), ErrorKind::Quota);
+        assert!(!error.is_retryable());
+    }
+}
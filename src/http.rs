@@ -1,6 +1,55 @@
 //! # LLM information post interface
 
-use std::borrow::Cow;
+use std::{borrow::Cow, sync::OnceLock};
+
+/// The connection pool shared by the free functions, so that even callers who do not hold a
+/// [`ZhipuClient`](crate::prelude::ZhipuClient) reuse TLS sessions between calls.
+pub(crate) fn shared_http_client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(reqwest::Client::new)
+}
+
+/// A request with any method, carrying `body` as JSON when there is one.
+pub(crate) fn api_request(
+    client: &reqwest::Client,
+    method: reqwest::Method,
+    api_url: &str,
+    api_key: &str,
+    body: Option<impl Into<reqwest::Body>>,
+) -> reqwest::RequestBuilder {
+    let request = client
+        .request(method, api_url)
+        .header("Authorization", format!("Bearer {}", api_key))
+        .header("Content-Type", "application/json");
+    match body {
+        Some(body) => request.body(body),
+        None => request,
+    }
+}
+
+pub(crate) fn post_request(
+    client: &reqwest::Client,
+    api_url: &str,
+    api_key: &str,
+    request_json: impl Into<reqwest::Body>,
+) -> reqwest::RequestBuilder {
+    client
+        .post(api_url)
+        .header("Authorization", format!("Bearer {}", api_key))
+        .header("Content-Type", "application/json")
+        .body(request_json)
+}
+
+pub(crate) fn get_request(
+    client: &reqwest::Client,
+    api_url: &str,
+    api_key: &str,
+) -> reqwest::RequestBuilder {
+    client
+        .get(api_url) // 使用 GET 方法
+        .header("Authorization", format!("Bearer {}", api_key))
+        .header("Content-Type", "application/json")
+}
 
 pub async fn post<'a>(
     api_url: impl Into<Cow<'a, str>>,
@@ -11,15 +60,14 @@ pub async fn post<'a>(
     let api_key = api_key.into();
     let request_json = request_json.into();
 
-    let client = reqwest::Client::new();
-    let response = client
-        .post(api_url.as_ref())
-        .header("Authorization", format!("Bearer {}", api_key))
-        .header("Content-Type", "application/json")
-        .body(request_json.into_owned())
-        .send()
-        .await;
-    response
+    post_request(
+        shared_http_client(),
+        &api_url,
+        &api_key,
+        request_json.into_owned(),
+    )
+    .send()
+    .await
 }
 
 pub async fn get<'a>(
@@ -29,12 +77,7 @@ pub async fn get<'a>(
     let api_url = api_url.into();
     let api_key = api_key.into();
 
-    let client = reqwest::Client::new();
-    let response = client
-        .get(api_url.as_ref()) // 使用 GET 方法
-        .header("Authorization", format!("Bearer {}", api_key))
-        .header("Content-Type", "application/json")
+    get_request(shared_http_client(), &api_url, &api_key)
         .send()
-        .await;
-    response
+        .await
 }
@@ -1,5 +1,24 @@
 pub mod api_resource;
+mod auth;
+mod client;
+mod endpoint;
 mod error;
 mod http;
+mod limit;
+mod middleware;
 pub mod prelude;
+mod retry;
 mod role;
+mod telemetry;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+
+#[cfg(feature = "macros")]
+pub use zhipuai_rs_macros::zhipu_tool;
+
+/// Used by the code `#[zhipu_tool]` generates.
+#[doc(hidden)]
+pub mod __private {
+    pub use serde;
+    pub use serde_json;
+}
@@ -0,0 +1,233 @@
+//! # Client-side rate limits
+//!
+//! The platform caps requests per minute, tokens per minute and concurrent requests per model.
+//! A [`RateLimiter`] given to [`ZhipuClientBuilder::rate_limiter`](crate::prelude::ZhipuClientBuilder::rate_limiter)
+//! enforces those caps before a request leaves the process. Callers over the limit wait in line
+//! (first come, first served) instead of failing.
+//!
+//! Token usage is not known before the response arrives, so TPM limits are enforced on an
+//! estimate: the [`EstimateTokens`](crate::api_resource::tokenizer::EstimateTokens) count of the
+//! prompt plus `max_tokens` when it is set.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{
+    sync::{Mutex as AsyncMutex, OwnedSemaphorePermit, Semaphore},
+    time::{Instant, sleep},
+};
+
+/// The limits applied to one model.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RateLimit {
+    rpm: Option<u32>,
+    tpm: Option<u32>,
+    max_in_flight: Option<usize>,
+}
+
+impl RateLimit {
+    /// No limit at all.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// set the maximum number of requests per minute
+    pub fn rpm(mut self, rpm: u32) -> Self {
+        self.rpm = Some(rpm.max(1));
+        self
+    }
+
+    /// set the maximum number of (estimated) tokens per minute
+    pub fn tpm(mut self, tpm: u32) -> Self {
+        self.tpm = Some(tpm.max(1));
+        self
+    }
+
+    /// set the maximum number of requests waiting for a response at the same time
+    pub fn max_in_flight(mut self, max_in_flight: usize) -> Self {
+        self.max_in_flight = Some(max_in_flight.max(1));
+        self
+    }
+
+    pub fn get_rpm(&self) -> Option<u32> {
+        self.rpm
+    }
+
+    pub fn get_tpm(&self) -> Option<u32> {
+        self.tpm
+    }
+
+    pub fn get_max_in_flight(&self) -> Option<usize> {
+        self.max_in_flight
+    }
+}
+
+/// Per-model rate and concurrency limits, keyed by the `model` of each request.
+/// ```ignore
+/// let limiter = RateLimiter::new()
+///     .model("glm-4-flash", RateLimit::new().rpm(60).max_in_flight(5))
+///     .default_limit(RateLimit::new().max_in_flight(2));
+/// let client = ZhipuClient::builder(&api_key).rate_limiter(limiter).build()?;
+/// ```
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    default_limit: RateLimit,
+    limits: HashMap<String, RateLimit>,
+    states: Mutex<HashMap<String, Arc<ModelState>>>,
+}
+
+impl RateLimiter {
+    /// A limiter without any limit; add some with [`Self::model`] or [`Self::default_limit`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// set the limits of one model
+    pub fn model(mut self, model: &str, limit: RateLimit) -> Self {
+        self.limits.insert(model.to_string(), limit);
+        self
+    }
+
+    /// set the limits of every model without its own entry. Each model still gets its own budget.
+    pub fn default_limit(mut self, limit: RateLimit) -> Self {
+        self.default_limit = limit;
+        self
+    }
+
+    /// The limits applied to `model`.
+    pub fn limit_for(&self, model: &str) -> &RateLimit {
+        self.limits.get(model).unwrap_or(&self.default_limit)
+    }
+
+    /// Wait until a request to `model` costing `tokens` fits in every limit. The returned permit
+    /// holds the concurrency slot and must be kept until the response has been consumed.
+    pub async fn acquire(&self, model: &str, tokens: u32) -> RatePermit {
+        let state = self.state(model);
+        // the concurrency slot is taken first, so a queued request does not burn rate budget
+        let permit = match &state.in_flight {
+            Some(semaphore) => semaphore.clone().acquire_owned().await.ok(),
+            None => None,
+        };
+        if let Some(bucket) = &state.requests {
+            bucket.take(1).await;
+        }
+        if let Some(bucket) = &state.tokens {
+            bucket.take(tokens).await;
+        }
+        RatePermit { _permit: permit }
+    }
+
+    fn state(&self, model: &str) -> Arc<ModelState> {
+        let mut states = self.states.lock().unwrap_or_else(|e| e.into_inner());
+        states
+            .entry(model.to_string())
+            .or_insert_with(|| Arc::new(ModelState::new(self.limit_for(model))))
+            .clone()
+    }
+}
+
+/// Proof that a request passed the [`RateLimiter`]. Dropping it frees the concurrency slot.
+#[derive(Debug)]
+pub struct RatePermit {
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+#[derive(Debug)]
+struct ModelState {
+    requests: Option<TokenBucket>,
+    tokens: Option<TokenBucket>,
+    in_flight: Option<Arc<Semaphore>>,
+}
+
+impl ModelState {
+    fn new(limit: &RateLimit) -> Self {
+        Self {
+            requests: limit.rpm.map(TokenBucket::per_minute),
+            tokens: limit.tpm.map(TokenBucket::per_minute),
+            in_flight: limit.max_in_flight.map(|n| Arc::new(Semaphore::new(n))),
+        }
+    }
+}
+
+/// A token bucket refilled continuously. Waiters are served in order because the tokio mutex
+/// is fair and the waiter holding it sleeps until its own cost is covered.
+#[derive(Debug)]
+struct TokenBucket {
+    capacity: f64,
+    per_second: f64,
+    state: AsyncMutex<BucketState>,
+}
+
+#[derive(Debug)]
+struct BucketState {
+    available: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn per_minute(limit: u32) -> Self {
+        let capacity = limit as f64;
+        Self {
+            capacity,
+            per_second: capacity / 60.0,
+            state: AsyncMutex::new(BucketState {
+                available: capacity,
+                updated: Instant::now(),
+            }),
+        }
+    }
+
+    async fn take(&self, cost: u32) {
+        // a request larger than the whole bucket would wait forever, let it drain the bucket instead
+        let cost = (cost as f64).min(self.capacity);
+        let mut state = self.state.lock().await;
+        loop {
+            let now = Instant::now();
+            let elapsed = now.duration_since(state.updated).as_secs_f64();
+            state.available = (state.available + elapsed * self.per_second).min(self.capacity);
+            state.updated = now;
+            if state.available >= cost {
+                state.available -= cost;
+                return;
+            }
+            let missing = cost - state.available;
+            sleep(Duration::from_secs_f64(missing / self.per_second)).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rpm_limit_queues_requests() {
+        let limiter = RateLimiter::new().model("glm-4-flash", RateLimit::new().rpm(2));
+        let start = Instant::now();
+        limiter.acquire("glm-4-flash", 0).await;
+        limiter.acquire("glm-4-flash", 0).await;
+        assert!(start.elapsed() < Duration::from_secs(1));
+        limiter.acquire("glm-4-flash", 0).await;
+        assert!(start.elapsed() >= Duration::from_secs(29));
+        // other models have their own, unlimited budget
+        let start = Instant::now();
+        limiter.acquire("glm-4-plus", 0).await;
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_max_in_flight() {
+        let limiter = Arc::new(RateLimiter::new().default_limit(RateLimit::new().max_in_flight(1)));
+        let first = limiter.acquire("glm-4", 0).await;
+        let waiting = tokio::spawn({
+            let limiter = limiter.clone();
+            async move { limiter.acquire("glm-4", 0).await }
+        });
+        sleep(Duration::from_secs(1)).await;
+        assert!(!waiting.is_finished());
+        drop(first);
+        waiting.await.unwrap();
+    }
+}
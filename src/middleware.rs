@@ -0,0 +1,235 @@
+//! # Request/response middleware
+//!
+//! Every HTTP call of a [`ZhipuClient`](crate::prelude::ZhipuClient) passes through the chain of
+//! [`Middleware`] registered with
+//! [`ZhipuClientBuilder::middleware`](crate::prelude::ZhipuClientBuilder::middleware), in the
+//! order they were added. A middleware receives the fully built request (URL, headers, body),
+//! may change it, and decides whether and how to call the rest of the chain through [`Next`].
+//! Retries run the whole chain again, so each attempt is observed separately.
+//! ```ignore
+//! struct Timing;
+//!
+//! impl Middleware for Timing {
+//!     fn handle<'a>(&'a self, request: Request, next: Next<'a>) -> BoxFuture<'a, Result<Response, ZhipuApiError>> {
+//!         Box::pin(async move {
+//!             let start = Instant::now();
+//!             let response = next.run(request).await;
+//!             println!("took {:?}", start.elapsed());
+//!             response
+//!         })
+//!     }
+//! }
+//! ```
+
+use crate::error::ZhipuApiError;
+use futures::future::BoxFuture;
+use log::{Level, log};
+use reqwest::{
+    Request, Response,
+    header::{AUTHORIZATION, HeaderMap, HeaderName, HeaderValue},
+};
+use std::{sync::Arc, time::Instant};
+
+/// A layer around the HTTP calls of a client.
+pub trait Middleware: Send + Sync + 'static {
+    /// Handle `request`, usually by calling `next.run(request)` and returning its response.
+    fn handle<'a>(
+        &'a self,
+        request: Request,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, Result<Response, ZhipuApiError>>;
+}
+
+/// The rest of the middleware chain, ending with the HTTP client itself.
+pub struct Next<'a> {
+    http: &'a reqwest::Client,
+    middlewares: &'a [Arc<dyn Middleware>],
+}
+
+impl<'a> Next<'a> {
+    pub(crate) fn new(http: &'a reqwest::Client, middlewares: &'a [Arc<dyn Middleware>]) -> Self {
+        Self { http, middlewares }
+    }
+
+    /// Pass `request` to the next middleware, or send it when none is left.
+    pub fn run(self, request: Request) -> BoxFuture<'a, Result<Response, ZhipuApiError>> {
+        match self.middlewares.split_first() {
+            Some((middleware, rest)) => middleware.handle(
+                request,
+                Next {
+                    http: self.http,
+                    middlewares: rest,
+                },
+            ),
+            None => Box::pin(async move { Ok(self.http.execute(request).await?) }),
+        }
+    }
+}
+
+/// Add headers to every request, replacing values already set under the same name.
+/// ```ignore
+/// let client = ZhipuClient::builder(&api_key)
+///     .middleware(HeaderMiddleware::new().header("X-Tenant", "team-a")?)
+///     .build()?;
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct HeaderMiddleware {
+    headers: HeaderMap,
+}
+
+impl HeaderMiddleware {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// add a header, failing on an invalid name or value
+    pub fn header(mut self, name: &str, value: &str) -> Result<Self, ZhipuApiError> {
+        self.headers.insert(
+            HeaderName::from_bytes(name.as_bytes())?,
+            HeaderValue::from_str(value)?,
+        );
+        Ok(self)
+    }
+}
+
+impl Middleware for HeaderMiddleware {
+    fn handle<'a>(
+        &'a self,
+        mut request: Request,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, Result<Response, ZhipuApiError>> {
+        for (name, value) in &self.headers {
+            request.headers_mut().insert(name, value.clone());
+        }
+        next.run(request)
+    }
+}
+
+/// Log every request and its outcome through the `log` crate. The `Authorization` header and
+/// any header added with [`Self::redact`] are never written out.
+#[derive(Debug, Clone)]
+pub struct LoggingMiddleware {
+    level: Level,
+    headers: bool,
+    body: bool,
+    redacted: Vec<HeaderName>,
+}
+
+impl Default for LoggingMiddleware {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LoggingMiddleware {
+    /// Log method, URL, status and latency at debug level.
+    pub fn new() -> Self {
+        Self {
+            level: Level::Debug,
+            headers: false,
+            body: false,
+            redacted: vec![AUTHORIZATION],
+        }
+    }
+
+    /// set the level of the log records
+    pub fn level(mut self, level: Level) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// also log the request headers, with sensitive ones redacted
+    pub fn headers(mut self, headers: bool) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    /// also log the request body. Prompts may contain personal data, so this is off by default.
+    pub fn body(mut self, body: bool) -> Self {
+        self.body = body;
+        self
+    }
+
+    /// never log the value of this header
+    pub fn redact(mut self, name: HeaderName) -> Self {
+        self.redacted.push(name);
+        self
+    }
+
+    fn format_headers(&self, headers: &HeaderMap) -> String {
+        headers
+            .iter()
+            .map(|(name, value)| {
+                if self.redacted.contains(name) {
+                    format!("{}: ***", name)
+                } else {
+                    format!("{}: {}", name, value.to_str().unwrap_or("<binary>"))
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+impl Middleware for LoggingMiddleware {
+    fn handle<'a>(
+        &'a self,
+        request: Request,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, Result<Response, ZhipuApiError>> {
+        Box::pin(async move {
+            let method = request.method().clone();
+            let url = request.url().clone();
+            log!(self.level, "--> {} {}", method, url);
+            if self.headers {
+                log!(
+                    self.level,
+                    "    [{}]",
+                    self.format_headers(request.headers())
+                );
+            }
+            if self.body
+                && let Some(body) = request.body().and_then(|b| b.as_bytes())
+            {
+                log!(self.level, "    {}", String::from_utf8_lossy(body));
+            }
+            let start = Instant::now();
+            let response = next.run(request).await;
+            match &response {
+                Ok(response) => log!(
+                    self.level,
+                    "<-- {} {} {} ({:?})",
+                    response.status(),
+                    method,
+                    url,
+                    start.elapsed()
+                ),
+                Err(e) => log!(
+                    self.level,
+                    "<-- failed {} {} ({:?}): {}",
+                    method,
+                    url,
+                    start.elapsed(),
+                    e
+                ),
+            }
+            response
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_logging_redacts_authorization() {
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer secret"));
+        headers.insert("x-tenant", HeaderValue::from_static("team-a"));
+        let line = LoggingMiddleware::new().format_headers(&headers);
+        assert!(!line.contains("secret"));
+        assert!(line.contains("authorization: ***"));
+        assert!(line.contains("x-tenant: team-a"));
+    }
+}
@@ -2,6 +2,7 @@
 
 pub use crate::api_resource::{
     BigModel,
+    capability::{Feature, ModelInfo, model_info},
     chat::*,
     rtav::{
         BetaFields as RealtimeBetaFields, ChatMode as RealtimeChatMode,
@@ -10,7 +11,13 @@ pub use crate::api_resource::{
         InputTokenDetails as RealtimeInputTokenDetails,
         OutputTokenDetails as RealtimeOutputTokenDetails, Session as RealtimeSession,
         TurnDetection as RealtimeTurnDetection, Usage as RealtimeUsage, start_realtime_session,
+        start_realtime_session_with, start_realtime_session_with_auth,
     },
+    tokenizer::EstimateTokens,
+};
+pub use crate::{
+    auth::*, client::*, endpoint::*, error::*, http::*, limit::*, middleware::*, retry::*, role::*,
 };
-pub use crate::{error::*, http::*, role::*};
 pub use futures::StreamExt;
+#[cfg(feature = "macros")]
+pub use zhipuai_rs_macros::zhipu_tool;
@@ -1,48 +1,49 @@
 //! # LLM role
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::fmt;
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
 pub enum Role {
     System,
     User,
     Assistant,
+    /// the result of a tool call
+    Tool,
+    /// any other role, such as `observation`, kept as sent
+    Other(String),
 }
 
-impl From<Role> for &str {
-    fn from(s: Role) -> Self {
-        match s {
-            Role::System => "system",
-            Role::User => "user",
-            Role::Assistant => "assistant",
+impl Role {
+    /// the name sent to the API
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::System => "system",
+            Self::User => "user",
+            Self::Assistant => "assistant",
+            Self::Tool => "tool",
+            Self::Other(role) => role,
         }
     }
 }
 
 impl From<Role> for String {
     fn from(s: Role) -> Self {
-        s.to_string()
+        match s {
+            Role::Other(role) => role,
+            role => role.as_str().to_string(),
+        }
     }
 }
 
-// Optionally, add AsRef<str> for &str references
 impl AsRef<str> for Role {
     fn as_ref(&self) -> &str {
-        match self {
-            Self::System => "system",
-            Self::User => "user",
-            Self::Assistant => "assistant",
-        }
+        self.as_str()
     }
 }
 
 impl fmt::Display for Role {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let role_str = match self {
-            Self::System => "system",
-            Self::User => "user",
-            Self::Assistant => "assistant",
-        };
-        write!(f, "{}", role_str)
+        f.write_str(self.as_str())
     }
 }
 
@@ -52,7 +53,47 @@ impl From<&str> for Role {
             "user" => Self::User,
             "assistant" => Self::Assistant,
             "system" => Self::System,
-            _ => unimplemented!(),
+            "tool" => Self::Tool,
+            other => Self::Other(other.to_string()),
+        }
+    }
+}
+
+impl From<String> for Role {
+    fn from(value: String) -> Self {
+        match Self::from(value.as_str()) {
+            Self::Other(_) => Self::Other(value),
+            role => role,
+        }
+    }
+}
+
+impl Serialize for Role {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Role {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unknown_roles_are_kept() {
+        for role in [Role::System, Role::User, Role::Assistant, Role::Tool] {
+            assert_eq!(Role::from(role.as_str()), role);
         }
+        assert_eq!(
+            Role::from("observation"),
+            Role::Other("observation".to_string())
+        );
+        assert_eq!(String::from(Role::from("observation")), "observation");
+        assert_eq!(serde_json::to_value(Role::Tool).unwrap(), "tool");
     }
 }
@@ -0,0 +1,137 @@
+//! # `tracing` instrumentation
+//!
+//! With the `tracing` feature every call of [`ZhipuClient`](crate::prelude::ZhipuClient) runs in a
+//! `zhipu.api` span carrying the model, endpoint, request id, status, latency and token usage,
+//! streaming calls also record the time to the first token, and every realtime session gets a
+//! `zhipu.realtime` span with an event per message. Without the feature these helpers compile to
+//! nothing.
+
+use crate::{api_resource::chat::Usage, error::ZhipuApiError};
+use std::future::Future;
+#[cfg(feature = "tracing")]
+use {
+    std::time::Instant,
+    tracing::{Instrument, Span, field::Empty},
+};
+
+/// The span of one API call.
+pub(crate) struct CallSpan {
+    #[cfg(feature = "tracing")]
+    span: Span,
+    #[cfg(feature = "tracing")]
+    start: Instant,
+    #[cfg(feature = "tracing")]
+    first_token: bool,
+}
+
+#[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+impl CallSpan {
+    pub(crate) fn new(api: &'static str, model: &str, url: &str, request_id: Option<&str>) -> Self {
+        Self {
+            #[cfg(feature = "tracing")]
+            span: tracing::info_span!(
+                "zhipu.api",
+                api,
+                model,
+                endpoint = url,
+                request_id,
+                status = Empty,
+                latency_ms = Empty,
+                ttft_ms = Empty,
+                prompt_tokens = Empty,
+                completion_tokens = Empty,
+                total_tokens = Empty,
+                error = Empty,
+            ),
+            #[cfg(feature = "tracing")]
+            start: Instant::now(),
+            #[cfg(feature = "tracing")]
+            first_token: false,
+        }
+    }
+
+    /// Run `future` inside the span.
+    pub(crate) fn instrument<F: Future>(&self, future: F) -> impl Future<Output = F::Output> {
+        #[cfg(feature = "tracing")]
+        return future.instrument(self.span.clone());
+        #[cfg(not(feature = "tracing"))]
+        future
+    }
+
+    pub(crate) fn record_status(&self, status: reqwest::StatusCode) {
+        #[cfg(feature = "tracing")]
+        self.span.record("status", status.as_u16());
+    }
+
+    pub(crate) fn record_usage(&self, usage: &Usage) {
+        #[cfg(feature = "tracing")]
+        {
+            self.span.record("prompt_tokens", usage.prompt_tokens());
+            self.span
+                .record("completion_tokens", usage.completion_tokens());
+            self.span.record("total_tokens", usage.total_tokens());
+        }
+    }
+
+    /// Record the time to the first token, only the first call has an effect.
+    pub(crate) fn record_first_token(&mut self) {
+        #[cfg(feature = "tracing")]
+        if !self.first_token {
+            self.first_token = true;
+            self.span
+                .record("ttft_ms", self.start.elapsed().as_millis() as u64);
+        }
+    }
+
+    /// Record the latency and the error, if any, and close the span.
+    pub(crate) fn finish(&self, error: Option<&ZhipuApiError>) {
+        #[cfg(feature = "tracing")]
+        {
+            self.span
+                .record("latency_ms", self.start.elapsed().as_millis() as u64);
+            if let Some(error) = error {
+                self.span.record("error", tracing::field::display(error));
+                if let Some(status) = error.status() {
+                    self.span.record("status", status);
+                }
+            }
+        }
+    }
+}
+
+/// The span of one realtime session, shared by both halves of the connection.
+#[derive(Clone)]
+pub(crate) struct SessionSpan {
+    #[cfg(feature = "tracing")]
+    span: Span,
+}
+
+#[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+impl SessionSpan {
+    pub(crate) fn new(url: &str) -> Self {
+        Self {
+            #[cfg(feature = "tracing")]
+            span: tracing::info_span!("zhipu.realtime", endpoint = url),
+        }
+    }
+
+    pub(crate) fn sent(&self, event_type: Option<&str>) {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(parent: &self.span, event_type, "realtime event sent");
+    }
+
+    pub(crate) fn received(&self, event_type: Option<&str>) {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(parent: &self.span, event_type, "realtime event received");
+    }
+
+    pub(crate) fn failed(&self, error: &ZhipuApiError) {
+        #[cfg(feature = "tracing")]
+        tracing::warn!(parent: &self.span, error = %error, "realtime session error");
+    }
+
+    pub(crate) fn closed(&self) {
+        #[cfg(feature = "tracing")]
+        tracing::info!(parent: &self.span, "realtime session closed");
+    }
+}
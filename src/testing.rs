@@ -0,0 +1,843 @@
+//! # Test support
+//!
+//! [`MockServer`] is an in-process HTTP stand-in for the platform, enabled with the `testing`
+//! feature. It listens on `127.0.0.1` and answers the chat, images, videos and async-result
+//! routes, so tests can run a real [`ZhipuClient`] without network access.
+//!
+//! Requests are answered, in order of precedence, by
+//! 1. the scripted [`Mock`]s, first match wins;
+//! 2. the cassette, when the server was started with [`MockServer::replay`] or
+//!    [`MockServer::record`];
+//! 3. the built-in [`fixtures`], which return a plausible success body for every known route.
+//!
+//! Every request is kept and can be inspected with [`MockServer::received`].
+//! ```ignore
+//! let server = MockServer::start().await?;
+//! server.mock(Mock::post("/chat/completions", MockResponse::error(429, "1302", "rate limited")));
+//! let client = server.client();
+//! let err = client.chat(&request).await.unwrap_err();
+//! assert_eq!(err.kind(), ErrorKind::RateLimited);
+//! assert_eq!(server.received()[0].json()["model"], "glm-4-flash");
+//! ```
+
+use crate::{client::ZhipuClient, endpoint::EndpointConfig, retry::RetryPolicy};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{Error as IoError, ErrorKind as IoErrorKind},
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream, tcp::OwnedWriteHalf},
+    task::JoinHandle,
+    time::sleep,
+};
+
+/// A local HTTP server standing in for the platform. It stops when dropped.
+pub struct MockServer {
+    addr: SocketAddr,
+    state: Arc<State>,
+    task: JoinHandle<()>,
+}
+
+struct State {
+    mocks: Mutex<Vec<Mock>>,
+    received: Mutex<Vec<ReceivedRequest>>,
+    cassette: Option<CassetteMode>,
+}
+
+enum CassetteMode {
+    Record {
+        upstream: String,
+        http: reqwest::Client,
+        path: PathBuf,
+        interactions: tokio::sync::Mutex<Vec<Interaction>>,
+    },
+    Replay(Mutex<VecDeque<Interaction>>),
+}
+
+impl MockServer {
+    /// Start a server answering from scripted mocks and the built-in fixtures.
+    pub async fn start() -> Result<Self, IoError> {
+        Self::with_cassette(None).await
+    }
+
+    /// Start a server that forwards every request not matched by a [`Mock`] to `upstream` (for
+    /// example [`crate::prelude::DEFAULT_BASE_URL`]) and saves the exchanges to the cassette at
+    /// `path`. The `Authorization` header is forwarded but never written to the cassette.
+    pub async fn record(upstream: &str, path: impl AsRef<Path>) -> Result<Self, IoError> {
+        Self::with_cassette(Some(CassetteMode::Record {
+            upstream: upstream.trim_end_matches('/').to_string(),
+            http: reqwest::Client::new(),
+            path: path.as_ref().to_path_buf(),
+            interactions: Default::default(),
+        }))
+        .await
+    }
+
+    /// Start a server answering from the cassette at `path`, written earlier by [`Self::record`].
+    /// Each recorded exchange is served once, in order, to the first request with the same
+    /// method and path.
+    pub async fn replay(path: impl AsRef<Path>) -> Result<Self, IoError> {
+        let interactions: Vec<Interaction> = serde_json::from_slice(&tokio::fs::read(path).await?)?;
+        Self::with_cassette(Some(CassetteMode::Replay(Mutex::new(interactions.into())))).await
+    }
+
+    async fn with_cassette(cassette: Option<CassetteMode>) -> Result<Self, IoError> {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(State {
+            mocks: Default::default(),
+            received: Default::default(),
+            cassette,
+        });
+        let task = tokio::spawn({
+            let state = state.clone();
+            async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    tokio::spawn(serve_connection(stream, state.clone()));
+                }
+            }
+        });
+        Ok(Self { addr, state, task })
+    }
+
+    /// The address the server listens on.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// The base URL to give to [`EndpointConfig::custom`], e.g. `http://127.0.0.1:38217`.
+    pub fn base_url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// An endpoint pointing at this server.
+    pub fn endpoint(&self) -> EndpointConfig {
+        EndpointConfig::custom(&self.base_url())
+    }
+
+    /// A client pointing at this server, with a dummy key and retries disabled.
+    pub fn client(&self) -> ZhipuClient {
+        ZhipuClient::builder("test-id.test-secret")
+            .endpoint(self.endpoint())
+            .retry_policy(RetryPolicy::none())
+            .build()
+            .expect("mock client must be constructible")
+    }
+
+    /// Script a response. Mocks are tried in the order they were added.
+    pub fn mock(&self, mock: Mock) -> &Self {
+        lock(&self.state.mocks).push(mock);
+        self
+    }
+
+    /// Every request received so far, oldest first.
+    pub fn received(&self) -> Vec<ReceivedRequest> {
+        lock(&self.state.received).clone()
+    }
+
+    /// Forget the requests received so far.
+    pub fn clear_received(&self) {
+        lock(&self.state.received).clear();
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// A scripted answer to requests with a given method and path.
+#[derive(Debug, Clone)]
+pub struct Mock {
+    method: String,
+    path: String,
+    times: Option<usize>,
+    response: MockResponse,
+}
+
+impl Mock {
+    /// Answer `method` requests to `path` (without the query string) with `response`, once.
+    pub fn new(method: &str, path: &str, response: MockResponse) -> Self {
+        Self {
+            method: method.to_ascii_uppercase(),
+            path: path.to_string(),
+            times: Some(1),
+            response,
+        }
+    }
+
+    pub fn post(path: &str, response: MockResponse) -> Self {
+        Self::new("POST", path, response)
+    }
+
+    pub fn get(path: &str, response: MockResponse) -> Self {
+        Self::new("GET", path, response)
+    }
+
+    /// answer the first `times` matching requests, default is 1
+    pub fn times(mut self, times: usize) -> Self {
+        self.times = Some(times);
+        self
+    }
+
+    /// answer every matching request
+    pub fn always(mut self) -> Self {
+        self.times = None;
+        self
+    }
+
+    fn matches(&self, request: &ReceivedRequest) -> bool {
+        self.times != Some(0) && self.method == request.method && self.path == request.path
+    }
+}
+
+/// What the server sends back.
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: MockBody,
+    delay: Duration,
+}
+
+#[derive(Debug, Clone)]
+enum MockBody {
+    Full {
+        content_type: String,
+        data: Vec<u8>,
+    },
+    Sse {
+        events: Vec<String>,
+        disconnect_after: Option<usize>,
+    },
+    Disconnect,
+}
+
+impl MockResponse {
+    /// `200 OK` with a JSON body.
+    pub fn json(value: &impl Serialize) -> Self {
+        Self::body(
+            "application/json",
+            serde_json::to_vec(value).expect("mock body must serialize"),
+        )
+    }
+
+    /// `200 OK` with a raw body.
+    pub fn body(content_type: &str, data: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status: 200,
+            headers: Vec::new(),
+            body: MockBody::Full {
+                content_type: content_type.to_string(),
+                data: data.into(),
+            },
+            delay: Duration::ZERO,
+        }
+    }
+
+    /// An error body the way the platform sends it: `{"error":{"code":..,"message":..}}`.
+    pub fn error(status: u16, code: &str, message: &str) -> Self {
+        Self::json(&json!({"error": {"code": code, "message": message}})).status(status)
+    }
+
+    /// A `text/event-stream` body with one `data:` event per value, followed by `data: [DONE]`.
+    pub fn sse(events: impl IntoIterator<Item = Value>) -> Self {
+        let mut events: Vec<String> = events.into_iter().map(|e| e.to_string()).collect();
+        events.push("[DONE]".to_string());
+        Self {
+            status: 200,
+            headers: Vec::new(),
+            body: MockBody::Sse {
+                events,
+                disconnect_after: None,
+            },
+            delay: Duration::ZERO,
+        }
+    }
+
+    /// Close the connection without answering.
+    pub fn disconnect() -> Self {
+        Self {
+            status: 0,
+            headers: Vec::new(),
+            body: MockBody::Disconnect,
+            delay: Duration::ZERO,
+        }
+    }
+
+    /// set the status code
+    pub fn status(mut self, status: u16) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// add a response header
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// wait before answering, and before every event of a stream
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// for a stream, drop the connection after sending `events` events
+    pub fn disconnect_after(mut self, events: usize) -> Self {
+        if let MockBody::Sse {
+            disconnect_after, ..
+        } = &mut self.body
+        {
+            *disconnect_after = Some(events);
+        }
+        self
+    }
+}
+
+/// A request received by the [`MockServer`].
+#[derive(Debug, Clone)]
+pub struct ReceivedRequest {
+    method: String,
+    path: String,
+    query: Option<String>,
+    headers: HashMap<String, String>,
+    body: Vec<u8>,
+}
+
+impl ReceivedRequest {
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+
+    /// the path without the query string
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn query(&self) -> Option<&str> {
+        self.query.as_deref()
+    }
+
+    /// a header by its case-insensitive name
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .get(&name.to_ascii_lowercase())
+            .map(String::as_str)
+    }
+
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// the body parsed as JSON, `Value::Null` when it is not JSON
+    pub fn json(&self) -> Value {
+        serde_json::from_slice(&self.body).unwrap_or_default()
+    }
+}
+
+/// One exchange stored in a cassette file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Interaction {
+    method: String,
+    path: String,
+    request_body: Value,
+    status: u16,
+    content_type: String,
+    body: String,
+}
+
+/// Success bodies shaped like the platform's, used when nothing else answers a request.
+pub mod fixtures {
+    use serde_json::{Value, json};
+
+    /// A chat completion answering `content`.
+    pub fn chat_completion(content: &str) -> Value {
+        json!({
+            "id": "mock-chat",
+            "created": 1_700_000_000,
+            "model": "glm-4-flash",
+            "request_id": "mock-request",
+            "choices": [{
+                "index": 0,
+                "finish_reason": "stop",
+                "message": {"role": "assistant", "content": content}
+            }],
+            "usage": {"prompt_tokens": 8, "completion_tokens": 4, "total_tokens": 12}
+        })
+    }
+
+    /// The chunks of a streamed chat completion, one per piece of `content`.
+    pub fn chat_chunks(pieces: &[&str]) -> Vec<Value> {
+        let mut chunks: Vec<Value> = pieces
+            .iter()
+            .map(|piece| {
+                json!({
+                    "id": "mock-chat",
+                    "created": 1_700_000_000,
+                    "model": "glm-4-flash",
+                    "choices": [{"index": 0, "delta": {"role": "assistant", "content": piece}}]
+                })
+            })
+            .collect();
+        chunks.push(json!({
+            "id": "mock-chat",
+            "created": 1_700_000_000,
+            "model": "glm-4-flash",
+            "choices": [{"index": 0, "finish_reason": "stop", "delta": {"role": "assistant", "content": ""}}],
+            "usage": {"prompt_tokens": 8, "completion_tokens": pieces.len(), "total_tokens": 8 + pieces.len()}
+        }));
+        chunks
+    }
+
+    /// A chat completion asking for function calls, each given as `(id, name, arguments)`.
+    pub fn chat_tool_calls(calls: &[(&str, &str, Value)]) -> Value {
+        let tool_calls: Vec<Value> = calls
+            .iter()
+            .enumerate()
+            .map(|(index, (id, name, arguments))| {
+                json!({
+                    "id": id,
+                    "type": "function",
+                    "index": index,
+                    "function": {"name": name, "arguments": arguments.to_string()}
+                })
+            })
+            .collect();
+        json!({
+            "id": "mock-chat",
+            "created": 1_700_000_000,
+            "model": "glm-4-flash",
+            "request_id": "mock-request",
+            "choices": [{
+                "index": 0,
+                "finish_reason": "tool_calls",
+                "message": {"role": "assistant", "tool_calls": tool_calls}
+            }],
+            "usage": {"prompt_tokens": 8, "completion_tokens": 4, "total_tokens": 12}
+        })
+    }
+
+    /// An image generation result pointing at `url`.
+    pub fn images(url: &str) -> Value {
+        json!({"created": 1_700_000_000, "data": [{"url": url}]})
+    }
+
+    /// A submitted video generation task.
+    pub fn videos_task(id: &str) -> Value {
+        json!({
+            "request_id": "mock-request",
+            "id": id,
+            "model": "cogvideox",
+            "task_status": "PROCESSING"
+        })
+    }
+
+    /// A finished video generation task.
+    pub fn videos_result(url: &str) -> Value {
+        json!({
+            "model": "cogvideox",
+            "request_id": "mock-request",
+            "task_status": "SUCCESS",
+            "video_result": [{"url": url, "cover_image_url": format!("{}.jpg", url)}]
+        })
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+async fn serve_connection(stream: TcpStream, state: Arc<State>) {
+    let (read, mut write) = stream.into_split();
+    let mut read = BufReader::new(read);
+    while let Ok(Some(request)) = read_request(&mut read).await {
+        lock(&state.received).push(request.clone());
+        let response = respond(&state, &request).await;
+        match write_response(&mut write, &response).await {
+            Ok(true) => continue,
+            _ => break,
+        }
+    }
+}
+
+async fn respond(state: &State, request: &ReceivedRequest) -> MockResponse {
+    let scripted = {
+        let mut mocks = lock(&state.mocks);
+        mocks.iter_mut().find(|m| m.matches(request)).map(|m| {
+            if let Some(times) = &mut m.times {
+                *times -= 1;
+            }
+            m.response.clone()
+        })
+    };
+    if let Some(response) = scripted {
+        return response;
+    }
+    match &state.cassette {
+        Some(CassetteMode::Replay(interactions)) => {
+            let mut interactions = lock(interactions);
+            match interactions
+                .iter()
+                .position(|i| i.method == request.method && i.path == request.path)
+            {
+                Some(index) => {
+                    let interaction = interactions.remove(index).expect("index is in range");
+                    MockResponse::body(&interaction.content_type, interaction.body)
+                        .status(interaction.status)
+                }
+                None => MockResponse::error(
+                    404,
+                    "mock",
+                    &format!(
+                        "no recorded interaction for {} {}",
+                        request.method, request.path
+                    ),
+                ),
+            }
+        }
+        Some(CassetteMode::Record {
+            upstream,
+            http,
+            path,
+            interactions,
+        }) => match forward(http, upstream, request).await {
+            Ok(interaction) => {
+                let response =
+                    MockResponse::body(&interaction.content_type, interaction.body.clone())
+                        .status(interaction.status);
+                let mut interactions = interactions.lock().await;
+                interactions.push(interaction);
+                if let Ok(data) = serde_json::to_vec_pretty(&*interactions) {
+                    let _ = tokio::fs::write(path, data).await;
+                }
+                response
+            }
+            Err(e) => MockResponse::error(502, "mock", &format!("upstream failed: {}", e)),
+        },
+        None => fixture(request),
+    }
+}
+
+async fn forward(
+    http: &reqwest::Client,
+    upstream: &str,
+    request: &ReceivedRequest,
+) -> Result<Interaction, reqwest::Error> {
+    let url = match &request.query {
+        Some(query) => format!("{}{}?{}", upstream, request.path, query),
+        None => format!("{}{}", upstream, request.path),
+    };
+    let mut builder = match request.method.as_str() {
+        "GET" => http.get(&url),
+        _ => http.post(&url).body(request.body.clone()),
+    };
+    for name in ["authorization", "content-type"] {
+        if let Some(value) = request.header(name) {
+            builder = builder.header(name, value);
+        }
+    }
+    let response = builder.send().await?;
+    let status = response.status().as_u16();
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("application/json")
+        .to_string();
+    Ok(Interaction {
+        method: request.method.clone(),
+        path: request.path.clone(),
+        request_body: request.json(),
+        status,
+        content_type,
+        body: response.text().await?,
+    })
+}
+
+fn fixture(request: &ReceivedRequest) -> MockResponse {
+    match (request.method.as_str(), request.path.as_str()) {
+        ("POST", "/chat/completions") => {
+            if request.json()["stream"] == Value::Bool(true) {
+                MockResponse::sse(fixtures::chat_chunks(&["Hello", " from", " mock"]))
+            } else {
+                MockResponse::json(&fixtures::chat_completion("Hello from mock"))
+            }
+        }
+        ("POST", "/images/generations") => {
+            MockResponse::json(&fixtures::images("https://mock.local/image.png"))
+        }
+        ("POST", "/videos/generations") => MockResponse::json(&fixtures::videos_task("mock-task")),
+        ("GET", path) if path.starts_with("/async-result/") => {
+            MockResponse::json(&fixtures::videos_result("https://mock.local/video.mp4"))
+        }
+        _ => MockResponse::error(
+            404,
+            "mock",
+            &format!("no route for {} {}", request.method, request.path),
+        ),
+    }
+}
+
+async fn read_request(
+    read: &mut BufReader<tokio::net::tcp::OwnedReadHalf>,
+) -> Result<Option<ReceivedRequest>, IoError> {
+    let mut line = String::new();
+    if read.read_line(&mut line).await? == 0 {
+        return Ok(None);
+    }
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err(IoError::new(IoErrorKind::InvalidData, "bad request line"));
+    };
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path.to_string(), Some(query.to_string())),
+        None => (target.to_string(), None),
+    };
+    let method = method.to_string();
+
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        if read.read_line(&mut line).await? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+        }
+    }
+    let length = headers
+        .get("content-length")
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(0);
+    let mut body = vec![0; length];
+    read.read_exact(&mut body).await?;
+    Ok(Some(ReceivedRequest {
+        method,
+        path,
+        query,
+        headers,
+        body,
+    }))
+}
+
+/// Write `response`, returning whether the connection can be reused.
+async fn write_response(
+    write: &mut OwnedWriteHalf,
+    response: &MockResponse,
+) -> Result<bool, IoError> {
+    if !response.delay.is_zero() {
+        sleep(response.delay).await;
+    }
+    let reason = reqwest::StatusCode::from_u16(response.status)
+        .ok()
+        .and_then(|s| s.canonical_reason())
+        .unwrap_or("Unknown");
+    let mut head = format!("HTTP/1.1 {} {}\r\n", response.status, reason);
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    match &response.body {
+        MockBody::Disconnect => Ok(false),
+        MockBody::Full { content_type, data } => {
+            head.push_str(&format!(
+                "Content-Type: {}\r\nContent-Length: {}\r\n\r\n",
+                content_type,
+                data.len()
+            ));
+            write.write_all(head.as_bytes()).await?;
+            write.write_all(data).await?;
+            write.flush().await?;
+            Ok(true)
+        }
+        MockBody::Sse {
+            events,
+            disconnect_after,
+        } => {
+            head.push_str("Content-Type: text/event-stream\r\nTransfer-Encoding: chunked\r\n\r\n");
+            write.write_all(head.as_bytes()).await?;
+            write.flush().await?;
+            for (index, event) in events.iter().enumerate() {
+                if Some(index) == *disconnect_after {
+                    // dropping the connection without the final chunk is an error for the client
+                    return Ok(false);
+                }
+                if index > 0 && !response.delay.is_zero() {
+                    sleep(response.delay).await;
+                }
+                let data = format!("data: {}\n\n", event);
+                write
+                    .write_all(format!("{:x}\r\n{}\r\n", data.len(), data).as_bytes())
+                    .await?;
+                write.flush().await?;
+            }
+            write.write_all(b"0\r\n\r\n").await?;
+            write.flush().await?;
+            Ok(true)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        api_resource::{
+            BigModel,
+            chat::{Chat, ChatResponseAccumulator, ChatStreamEvent, Context, Message},
+            images::Images,
+            videos::Videos,
+        },
+        error::ErrorKind,
+    };
+    use futures::StreamExt;
+
+    fn chat_request(stream: bool) -> crate::api_resource::chat::ChatApiRequest {
+        let (_, request) = BigModel::<Chat>::new("glm-4-flash")
+            .add_message(Message::new(
+                "user",
+                Some(Context::simple_context("hi")),
+                None,
+            ))
+            .stream_enable(stream)
+            .build();
+        request
+    }
+
+    #[tokio::test]
+    async fn test_fixtures_and_recorded_requests() {
+        let server = MockServer::start().await.unwrap();
+        let client = server.client();
+
+        let response = client.chat(&chat_request(false)).await.unwrap();
+        assert_eq!(response.get_usage().unwrap().total_tokens(), 12);
+
+        let events: Vec<ChatStreamEvent> = client
+            .chat_stream(&chat_request(true))
+            .await
+            .unwrap()
+            .map(|e| e.unwrap())
+            .collect()
+            .await;
+        let content: String = events.iter().filter_map(|e| e.content()).collect();
+        assert_eq!(content, "Hello from mock");
+        assert_eq!(events.last(), Some(&ChatStreamEvent::Done));
+
+        let request = chat_request(true);
+        let mut chunks = std::pin::pin!(client.chat_stream_chunks(&request).await.unwrap());
+        let mut accumulator = ChatResponseAccumulator::new();
+        while let Some(chunk) = chunks.next().await {
+            accumulator.push_chunk(&chunk.unwrap());
+        }
+        let response = accumulator.response();
+        assert_eq!(response.get_id(), Some("mock-chat"));
+        assert_eq!(response.get_model(), Some("glm-4-flash"));
+
+        let (_, request) = BigModel::<Images>::new("cogview-3").prompt("a cat").build();
+        let images = client.images(&request).await.unwrap();
+        assert_eq!(images.urls(), ["https://mock.local/image.png"]);
+
+        let (_, request) = BigModel::<Videos>::new("cogvideox").prompt("a cat").build();
+        let task = client.videos(&request).await.unwrap();
+        let result = client.videos_result(&task.id()).await.unwrap();
+        assert_eq!(result.task_status(), "SUCCESS");
+
+        let received = server.received();
+        assert_eq!(received.len(), 6);
+        assert_eq!(received[0].json()["model"], "glm-4-flash");
+        assert_eq!(
+            received[0].header("Authorization"),
+            Some("Bearer test-id.test-secret")
+        );
+        assert_eq!(received[5].path(), "/async-result/mock-task");
+    }
+
+    #[tokio::test]
+    async fn test_scripted_errors_and_disconnects() {
+        let server = MockServer::start().await.unwrap();
+        server
+            .mock(Mock::post(
+                "/chat/completions",
+                MockResponse::error(429, "1302", "too many requests"),
+            ))
+            .mock(Mock::post(
+                "/chat/completions",
+                MockResponse::sse(fixtures::chat_chunks(&["a", "b", "c"])).disconnect_after(2),
+            ));
+        let client = server.client();
+
+        let error = client.chat(&chat_request(false)).await.unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::RateLimited);
+        assert_eq!(error.code(), Some("1302"));
+
+        let chunks: Vec<_> = client
+            .chat_stream(&chat_request(true))
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(
+            chunks[0].as_ref().unwrap(),
+            &ChatStreamEvent::ContentDelta("a".to_string())
+        );
+        assert!(chunks.last().unwrap().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_retry_against_mock() {
+        let server = MockServer::start().await.unwrap();
+        server.mock(Mock::post("/chat/completions", MockResponse::disconnect()));
+        let client = ZhipuClient::builder("key")
+            .endpoint(server.endpoint())
+            .retry_policy(RetryPolicy::new().initial_backoff(Duration::from_millis(1)))
+            .build()
+            .unwrap();
+        client.chat(&chat_request(false)).await.unwrap();
+
+        let received = server.received();
+        assert_eq!(received.len(), 2);
+        assert_eq!(
+            received[0].json()["request_id"],
+            received[1].json()["request_id"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_record_then_replay() {
+        let cassette = std::env::temp_dir().join(format!(
+            "zhipuai-rs-cassette-{}.json",
+            crate::retry::generate_request_id()
+        ));
+        let upstream = MockServer::start().await.unwrap();
+        upstream.mock(Mock::post(
+            "/chat/completions",
+            MockResponse::json(&fixtures::chat_completion("recorded")),
+        ));
+        let recorder = MockServer::record(&upstream.base_url(), &cassette)
+            .await
+            .unwrap();
+        recorder.client().chat(&chat_request(false)).await.unwrap();
+        let recorded = std::fs::read_to_string(&cassette).unwrap();
+        assert!(recorded.contains("recorded"));
+        assert!(!recorded.contains("test-secret"));
+        drop(upstream);
+
+        let replay = MockServer::replay(&cassette).await.unwrap();
+        let response = replay.client().chat(&chat_request(false)).await.unwrap();
+        let message = response.get_choices().unwrap()[0].message();
+        assert_eq!(message.simple_context().unwrap().1, "recorded");
+        std::fs::remove_file(cassette).unwrap();
+    }
+}
@@ -0,0 +1,251 @@
+//! # zhipuai-rs macros
+//!
+//! Use them through `zhipuai-rs` with its `macros` feature, not directly.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    Attribute, Error, Expr, ExprLit, FnArg, ItemFn, Lit, LitStr, Meta, Pat, Path, Result, Type,
+    parse_macro_input, parse_quote,
+};
+
+/// Turn a function into a tool the model can call.
+///
+/// The function keeps its signature. Next to it, a module of the same name holds:
+/// - `NAME`, the name of the tool, the name of the function unless set with `name = "..."`;
+/// - `function()`, the `Function` definition, described by the doc comments of the function,
+///   its parameters described by `#[param(description = "...")]` and typed by `ToolParameter`,
+///   or given whole by `#[param(schema = <Property expression>)]`;
+/// - `Arguments`, the parameters as a struct deserialized from the arguments of the call;
+/// - `call(arguments)`, which parses the arguments and runs the function;
+/// - `dispatch(&tool_call)`, the same for a `ToolCall`, `None` when it is for another tool;
+/// - `register(registry)`, which adds the tool to a `ToolRegistry`.
+///
+/// Parameters are owned types, `Option` ones are optional. The function returns a `Result` whose
+/// `Ok` value is serializable; strings are sent to the model as they are, anything else as JSON.
+/// ```ignore
+/// /// Query the trains between two stations
+/// #[zhipu_tool]
+/// async fn query_train_info(
+///     #[param(description = "departure station")] from: String,
+///     #[param(description = "arrival station")] to: String,
+///     #[param(schema = Property::string("travel date").format("date"))] date: NaiveDate,
+/// ) -> anyhow::Result<Vec<Train>> {
+///     ...
+/// }
+///
+/// let registry = query_train_info::register(ToolRegistry::new());
+/// ```
+#[proc_macro_attribute]
+pub fn zhipu_tool(args: TokenStream, input: TokenStream) -> TokenStream {
+    let mut options = Options {
+        name: None,
+        krate: parse_quote!(::zhipuai_rs),
+    };
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("name") {
+            options.name = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("crate") {
+            options.krate = meta.value()?.parse()?;
+            Ok(())
+        } else {
+            Err(meta.error("expected `name` or `crate`"))
+        }
+    });
+    parse_macro_input!(args with parser);
+    let function = parse_macro_input!(input as ItemFn);
+    expand(options, function)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+struct Options {
+    name: Option<LitStr>,
+    krate: Path,
+}
+
+/// A parameter of the function.
+struct Parameter {
+    ident: syn::Ident,
+    ty: Type,
+    description: String,
+    schema: Option<Expr>,
+}
+
+fn expand(options: Options, mut function: ItemFn) -> Result<TokenStream2> {
+    let signature = &function.sig;
+    if !signature.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &signature.generics,
+            "a tool cannot be generic",
+        ));
+    }
+    let mut parameters = Vec::new();
+    for input in function.sig.inputs.iter_mut() {
+        let FnArg::Typed(input) = input else {
+            return Err(Error::new_spanned(input, "a tool cannot take `self`"));
+        };
+        let Pat::Ident(pattern) = &*input.pat else {
+            return Err(Error::new_spanned(
+                &input.pat,
+                "the parameters of a tool must be plain names",
+            ));
+        };
+        let (description, schema) = parameter_attributes(&input.attrs)?;
+        input.attrs.retain(|attr| !attr.path().is_ident("param"));
+        parameters.push(Parameter {
+            ident: pattern.ident.clone(),
+            ty: (*input.ty).clone(),
+            description,
+            schema,
+        });
+    }
+
+    let krate = &options.krate;
+    let serde = quote!(#krate::__private::serde);
+    let serde_path = LitStr::new(
+        &serde.to_string().replace(' ', ""),
+        proc_macro2::Span::call_site(),
+    );
+    let visibility = &function.vis;
+    let ident = &function.sig.ident;
+    let name = options
+        .name
+        .unwrap_or_else(|| LitStr::new(&ident.to_string(), ident.span()));
+    let description = documentation(&function.attrs);
+    let module_doc = format!("The tool made of `{}` by `#[zhipu_tool]`.", ident);
+    let fields = parameters
+        .iter()
+        .map(|Parameter { ident, ty, .. }| quote!(pub #ident: #ty));
+    let names: Vec<_> = parameters.iter().map(|p| &p.ident).collect();
+    let properties = parameters.iter().map(|parameter| {
+        let Parameter {
+            ident,
+            ty,
+            description,
+            schema,
+        } = parameter;
+        let key = ident.to_string();
+        let property = match schema {
+            Some(schema) => quote!(#schema),
+            None => quote!(<#ty as #krate::prelude::ToolParameter>::property(#description)),
+        };
+        if is_option(ty) {
+            quote!(.optional_property(#key, #property))
+        } else {
+            quote!(.property(#key, #property))
+        }
+    });
+    let invoke = if function.sig.asyncness.is_some() {
+        quote!(super::#ident(#(#names),*).await)
+    } else {
+        quote!(super::#ident(#(#names),*))
+    };
+
+    Ok(quote! {
+        #function
+
+        #[doc = #module_doc]
+        #visibility mod #ident {
+            #[allow(unused_imports)]
+            use super::*;
+
+            /// the name of the tool
+            pub const NAME: &str = #name;
+
+            /// the parameters of the tool
+            #[derive(#serde::Deserialize)]
+            #[serde(crate = #serde_path)]
+            pub struct Arguments {
+                #(#fields,)*
+            }
+
+            /// the definition sent to the model
+            pub fn function() -> #krate::prelude::Function {
+                #krate::prelude::Function::new(
+                    NAME,
+                    #description,
+                    #krate::prelude::Parameters::default() #(#properties)*,
+                )
+            }
+
+            /// parse `arguments` and run the function
+            pub async fn call(
+                arguments: #krate::__private::serde_json::Value,
+            ) -> #krate::prelude::ToolResult {
+                let Arguments { #(#names),* } =
+                    #krate::__private::serde_json::from_value(arguments)?;
+                #krate::prelude::IntoToolResult::into_tool_result(#invoke)
+            }
+
+            /// run the function if `tool_call` is for it
+            pub async fn dispatch(
+                tool_call: &#krate::prelude::ToolCall,
+            ) -> ::core::option::Option<#krate::prelude::ToolResult> {
+                let function = tool_call.function().filter(|function| function.name() == NAME)?;
+                ::core::option::Option::Some(call(function.arguments().clone()).await)
+            }
+
+            /// add the tool to `registry`
+            pub fn register(
+                registry: #krate::prelude::ToolRegistry,
+            ) -> #krate::prelude::ToolRegistry {
+                registry.register(function(), call)
+            }
+        }
+    })
+}
+
+/// The doc comments, one line each, without the space after `///`.
+fn documentation(attrs: &[Attribute]) -> String {
+    attrs
+        .iter()
+        .filter_map(|attr| match &attr.meta {
+            Meta::NameValue(meta) if meta.path.is_ident("doc") => match &meta.value {
+                Expr::Lit(ExprLit {
+                    lit: Lit::Str(text),
+                    ..
+                }) => Some(text.value().trim().to_string()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+/// The `description` and `schema` of `#[param(...)]`.
+fn parameter_attributes(attrs: &[Attribute]) -> Result<(String, Option<Expr>)> {
+    let mut description = String::new();
+    let mut schema = None;
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("param")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("description") {
+                description = meta.value()?.parse::<LitStr>()?.value();
+                Ok(())
+            } else if meta.path.is_ident("schema") {
+                schema = Some(meta.value()?.parse::<Expr>()?);
+                Ok(())
+            } else {
+                Err(meta.error("expected `description` or `schema`"))
+            }
+        })?;
+    }
+    Ok((description, schema))
+}
+
+/// Whether the type is written `Option<...>`.
+fn is_option(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Option"),
+        _ => false,
+    }
+}